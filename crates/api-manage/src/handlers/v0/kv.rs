@@ -0,0 +1,177 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::models::{KVEntry, KVNamespace, KVValue};
+use actix_web::{
+    delete, get, put,
+    web::{Data, Json, Path},
+    HttpResponse,
+};
+use std::collections::HashMap;
+use wws_data_kv::{errors::KVError, KV};
+
+/// Return the list of K/V namespaces and the number of keys they contain.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Returns all the K/V namespaces", body = [KVNamespace])
+    )
+)]
+#[get("/_api/v0/kv")]
+pub async fn handle_api_kv_namespaces(kv: Data<KV>) -> HttpResponse {
+    let mut namespaces: Vec<KVNamespace> = kv
+        .namespaces()
+        .iter()
+        .filter_map(|namespace| kv.find_store(namespace))
+        .map(|store| KVNamespace::from(store.as_ref()))
+        .collect();
+    namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    HttpResponse::Ok().json(namespaces)
+}
+
+/// Export the content of a K/V namespace as a JSON object. Expired keys are not included
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Return the keys and values of the namespace", body = HashMap<String, String>),
+        (status = 404, description = "The namespace is not present")
+    ),
+    params(
+        ("namespace" = String, Path, description = "K/V namespace"),
+    )
+)]
+#[get("/_api/v0/kv/{namespace}")]
+pub async fn handle_api_kv_export(kv: Data<KV>, path: Path<String>) -> HttpResponse {
+    if let Some(store) = kv.find_store(&path) {
+        HttpResponse::Ok().json(store.clone())
+    } else {
+        HttpResponse::NotFound().json("{}")
+    }
+}
+
+/// Import a JSON object into a K/V namespace. It replaces the current content of the
+/// namespace. The namespace must be configured by a worker, so it keeps its backend
+/// and quotas
+#[utoipa::path(
+    request_body = HashMap<String, String>,
+    responses(
+        (status = 200, description = "The namespace content was replaced"),
+        (status = 404, description = "The namespace is not present"),
+        (status = 507, description = "The content exceeds the namespace quota"),
+        (status = 500, description = "The content couldn't be persisted")
+    ),
+    params(
+        ("namespace" = String, Path, description = "K/V namespace"),
+    )
+)]
+#[put("/_api/v0/kv/{namespace}")]
+pub async fn handle_api_kv_import(
+    kv: Data<KV>,
+    path: Path<String>,
+    body: Json<HashMap<String, String>>,
+) -> HttpResponse {
+    let Some(store) = kv.find_store(&path) else {
+        return HttpResponse::NotFound().json("{}");
+    };
+
+    match store.replace(body.into_inner()) {
+        Ok(_) => HttpResponse::Ok().json("{}"),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Return the value of a key in a K/V namespace.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Return the key and its value", body = KVEntry),
+        (status = 404, description = "The namespace or the key are not present")
+    ),
+    params(
+        ("namespace" = String, Path, description = "K/V namespace"),
+        ("key" = String, Path, description = "Key in the namespace"),
+    )
+)]
+#[get("/_api/v0/kv/{namespace}/{key}")]
+pub async fn handle_api_kv_get(kv: Data<KV>, path: Path<(String, String)>) -> HttpResponse {
+    let (namespace, key) = path.into_inner();
+    let value = kv.find_store(&namespace).and_then(|store| store.get(&key));
+
+    if let Some(value) = value {
+        HttpResponse::Ok().json(KVEntry { key, value })
+    } else {
+        HttpResponse::NotFound().json("{}")
+    }
+}
+
+/// Set the value of a key in a K/V namespace. Without a TTL, the key uses the
+/// default one of the namespace
+#[utoipa::path(
+    request_body = KVValue,
+    responses(
+        (status = 200, description = "The key was set"),
+        (status = 404, description = "The namespace is not present"),
+        (status = 507, description = "The key exceeds the namespace quota"),
+        (status = 500, description = "The key couldn't be persisted")
+    ),
+    params(
+        ("namespace" = String, Path, description = "K/V namespace"),
+        ("key" = String, Path, description = "Key in the namespace"),
+    )
+)]
+#[put("/_api/v0/kv/{namespace}/{key}")]
+pub async fn handle_api_kv_set(
+    kv: Data<KV>,
+    path: Path<(String, String)>,
+    body: Json<KVValue>,
+) -> HttpResponse {
+    let (namespace, key) = path.into_inner();
+    let Some(store) = kv.find_store(&namespace) else {
+        return HttpResponse::NotFound().json("{}");
+    };
+
+    let result = match body.ttl_secs {
+        Some(ttl_secs) => store.set_with_ttl(&key, &body.value, ttl_secs),
+        None => store.set(&key, &body.value),
+    };
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json("{}"),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Delete a key from a K/V namespace.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The key was deleted"),
+        (status = 404, description = "The namespace or the key are not present"),
+        (status = 500, description = "The change couldn't be persisted")
+    ),
+    params(
+        ("namespace" = String, Path, description = "K/V namespace"),
+        ("key" = String, Path, description = "Key in the namespace"),
+    )
+)]
+#[delete("/_api/v0/kv/{namespace}/{key}")]
+pub async fn handle_api_kv_delete(kv: Data<KV>, path: Path<(String, String)>) -> HttpResponse {
+    let (namespace, key) = path.into_inner();
+    let store = kv
+        .find_store(&namespace)
+        .filter(|store| store.get(&key).is_some());
+
+    let Some(store) = store else {
+        return HttpResponse::NotFound().json("{}");
+    };
+
+    match store.delete(&key) {
+        Ok(_) => HttpResponse::Ok().json("{}"),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Build the response for an error of the K/V store
+fn error_response(err: KVError) -> HttpResponse {
+    match err {
+        KVError::QuotaExceeded { .. } => HttpResponse::InsufficientStorage().json(err.to_string()),
+        _ => HttpResponse::InternalServerError().json(err.to_string()),
+    }
+}
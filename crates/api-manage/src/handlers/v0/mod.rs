@@ -1,4 +1,5 @@
 // Copyright 2023 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod kv;
 pub mod workers;
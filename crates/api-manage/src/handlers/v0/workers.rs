@@ -7,6 +7,7 @@ use actix_web::{
     web::{Data, Json, Path},
     HttpResponse, Responder, Result,
 };
+use std::sync::RwLock;
 use wws_router::{Routes, WORKERS};
 
 /// Return the list of loaded workers.
@@ -16,8 +17,14 @@ use wws_router::{Routes, WORKERS};
     )
 )]
 #[get("/_api/v0/workers")]
-pub async fn handle_api_workers(routes: Data<Routes>) -> Result<impl Responder> {
-    let workers: Vec<Worker> = routes.routes.iter().map(Worker::from).collect();
+pub async fn handle_api_workers(routes: Data<RwLock<Routes>>) -> Result<impl Responder> {
+    let workers: Vec<Worker> = routes
+        .read()
+        .expect("error locking routes lock for reading")
+        .routes
+        .iter()
+        .map(Worker::from)
+        .collect();
 
     Ok(Json(workers))
 }
@@ -33,7 +40,10 @@ pub async fn handle_api_workers(routes: Data<Routes>) -> Result<impl Responder>
     )
 )]
 #[get("/_api/v0/workers/{id}")]
-pub async fn handle_api_worker(routes: Data<Routes>, path: Path<String>) -> HttpResponse {
+pub async fn handle_api_worker(routes: Data<RwLock<Routes>>, path: Path<String>) -> HttpResponse {
+    let routes = routes
+        .read()
+        .expect("error locking routes lock for reading");
     let workers = WORKERS
         .read()
         .expect("error locking worker lock for reading");
@@ -41,7 +51,7 @@ pub async fn handle_api_worker(routes: Data<Routes>, path: Path<String>) -> Http
         .routes
         .iter()
         .find(|r| &r.worker == path.as_ref())
-        .map(|r| workers.get(&r.worker).expect("unexpected missing worker"));
+        .and_then(|r| workers.get(&r.worker));
 
     if let Some(worker) = worker {
         HttpResponse::Ok().json(WorkerConfig::from(worker.as_ref()))
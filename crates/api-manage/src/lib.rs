@@ -5,7 +5,10 @@ mod handlers;
 mod models;
 
 use actix_web::web::ServiceConfig;
-use models::Worker;
+use models::{
+    KVEntry, KVNamespace, KVValue, Worker, WorkerConfig, WorkerConfigData, WorkerFolder,
+    WorkerLimits,
+};
 use utoipa::OpenApi;
 
 /// Add the administration panel HTTP handlers to an existing
@@ -13,6 +16,12 @@ use utoipa::OpenApi;
 pub fn config_manage_api_handlers(cfg: &mut ServiceConfig) {
     cfg.service(handlers::v0::workers::handle_api_workers);
     cfg.service(handlers::v0::workers::handle_api_worker);
+    cfg.service(handlers::v0::kv::handle_api_kv_namespaces);
+    cfg.service(handlers::v0::kv::handle_api_kv_export);
+    cfg.service(handlers::v0::kv::handle_api_kv_import);
+    cfg.service(handlers::v0::kv::handle_api_kv_get);
+    cfg.service(handlers::v0::kv::handle_api_kv_set);
+    cfg.service(handlers::v0::kv::handle_api_kv_delete);
 }
 
 #[derive(OpenApi)]
@@ -29,8 +38,23 @@ pub fn config_manage_api_handlers(cfg: &mut ServiceConfig) {
     ),
     paths(
         handlers::v0::workers::handle_api_workers,
-        handlers::v0::workers::handle_api_worker
+        handlers::v0::workers::handle_api_worker,
+        handlers::v0::kv::handle_api_kv_namespaces,
+        handlers::v0::kv::handle_api_kv_export,
+        handlers::v0::kv::handle_api_kv_import,
+        handlers::v0::kv::handle_api_kv_get,
+        handlers::v0::kv::handle_api_kv_set,
+        handlers::v0::kv::handle_api_kv_delete
     ),
-    components(schemas(Worker))
+    components(schemas(
+        Worker,
+        WorkerConfig,
+        WorkerConfigData,
+        WorkerFolder,
+        WorkerLimits,
+        KVNamespace,
+        KVEntry,
+        KVValue
+    ))
 )]
 pub struct ApiDoc;
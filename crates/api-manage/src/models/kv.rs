@@ -0,0 +1,48 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use wws_data_kv::KVStore;
+
+#[derive(Serialize, ToSchema)]
+/// A Key / Value namespace used by the workers
+pub struct KVNamespace {
+    /// Name of the namespace
+    #[schema(example = "counter")]
+    pub name: String,
+    /// Number of keys in the namespace
+    #[schema(example = 10)]
+    keys: usize,
+}
+
+impl From<&KVStore> for KVNamespace {
+    fn from(value: &KVStore) -> Self {
+        Self {
+            name: value.namespace.clone(),
+            keys: value.keys().len(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+/// A key and its value in a Key / Value namespace
+pub struct KVEntry {
+    /// The key
+    #[schema(example = "counter")]
+    pub key: String,
+    /// The value associated to the key
+    #[schema(example = "1")]
+    pub value: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+/// The new value of a key
+pub struct KVValue {
+    /// The value to set
+    #[schema(example = "1")]
+    pub value: String,
+    /// Optional time to live in seconds. The key expires after it
+    #[schema(example = 60)]
+    pub ttl_secs: Option<u64>,
+}
@@ -1,8 +1,10 @@
 // Copyright 2023 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod kv;
 mod worker;
 mod worker_config;
 
+pub use kv::{KVEntry, KVNamespace, KVValue};
 pub use worker::Worker;
-pub use worker_config::WorkerConfig;
+pub use worker_config::{WorkerConfig, WorkerConfigData, WorkerFolder, WorkerLimits};
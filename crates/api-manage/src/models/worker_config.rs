@@ -7,6 +7,7 @@ use serde::Serialize;
 use utoipa::ToSchema;
 use wws_worker::{
     features::{data::ConfigData, folders::Folder},
+    limits::LimitsConfig,
     Worker,
 };
 
@@ -28,6 +29,8 @@ pub struct WorkerConfig {
     pub folders: Vec<WorkerFolder>,
     /// Environment variables
     pub vars: HashMap<String, String>,
+    /// Execution limits
+    pub limits: WorkerLimits,
 }
 
 impl From<&Worker> for WorkerConfig {
@@ -55,6 +58,7 @@ impl From<&Worker> for WorkerConfig {
             data: WorkerConfigData::from(config.data.as_ref()),
             folders,
             vars: config.vars.clone(),
+            limits: WorkerLimits::from(&config.limits),
         }
     }
 }
@@ -95,3 +99,39 @@ impl From<&Folder> for WorkerFolder {
         }
     }
 }
+
+#[derive(Serialize, ToSchema)]
+/// Execution limits for this specific worker. Empty values mean no limit
+pub struct WorkerLimits {
+    /// Maximum time in milliseconds to process a request
+    #[schema(example = 500)]
+    timeout_ms: Option<u64>,
+    /// Maximum amount of fuel to process a request
+    #[schema(example = 100000000)]
+    fuel: Option<u64>,
+    /// Maximum size in megabytes of the worker memory
+    #[schema(example = 64)]
+    max_memory_mb: Option<u64>,
+    /// Maximum number of tables
+    #[schema(example = 10)]
+    max_tables: Option<usize>,
+    /// Maximum number of instances
+    #[schema(example = 10)]
+    max_instances: Option<usize>,
+    /// Maximum size in bytes of the response
+    #[schema(example = 1048576)]
+    max_response_bytes: Option<usize>,
+}
+
+impl From<&LimitsConfig> for WorkerLimits {
+    fn from(value: &LimitsConfig) -> Self {
+        Self {
+            timeout_ms: value.timeout_ms,
+            fuel: value.fuel,
+            max_memory_mb: value.max_memory_mb,
+            max_tables: value.max_tables,
+            max_instances: value.max_instances,
+            max_response_bytes: value.max_response_bytes,
+        }
+    }
+}
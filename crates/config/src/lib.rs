@@ -19,6 +19,9 @@ pub const DEFAULT_REPO_URL: &str = "https://workers.wasmlabs.dev/repository/v1/i
 /// Config file name
 const CONFIG_FILENAME: &str = ".wws.toml";
 
+/// Routes manifest file name
+const ROUTES_FILENAME: &str = "routes.toml";
+
 /// Loads the data from the Project definition file or .wws.toml.
 /// This file contains information about the different runtimes
 /// required for this project. You can think of those as dependencies.
@@ -35,6 +38,18 @@ pub struct Config {
     version: u32,
     /// List of repositories
     pub repositories: Vec<ConfigRepository>,
+    /// Default settings for the data connectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<ConfigData>,
+    /// Default network settings for the HTTP requests of the workers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_requests: Option<ConfigHttpRequests>,
+    /// Explicit routes, redirects and rewrites
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routes: Option<ConfigRoutes>,
+    /// Other projects served by the same server, based on the request host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<ConfigHost>,
 }
 
 impl Config {
@@ -122,6 +137,44 @@ impl Config {
         project_root.join(CONFIG_FILENAME)
     }
 
+    /// Load the explicit routes of the project. They come from the
+    /// `routes.toml` file and the `routes` section of this file.
+    /// The file is read every time, so it reflects the latest changes.
+    pub fn load_routes(&self, project_root: &Path) -> Result<ConfigRoutes> {
+        let routes_path = project_root.join(ROUTES_FILENAME);
+        let mut routes: ConfigRoutes = if routes_path.exists() {
+            toml::from_str(&fs::read_to_string(routes_path)?)?
+        } else {
+            ConfigRoutes::default()
+        };
+
+        if let Some(config_routes) = &self.routes {
+            routes.workers.extend(config_routes.workers.iter().cloned());
+            routes
+                .redirects
+                .extend(config_routes.redirects.iter().cloned());
+            routes
+                .rewrites
+                .extend(config_routes.rewrites.iter().cloned());
+
+            // The routes file has more priority
+            let errors = &config_routes.errors;
+            routes.errors.not_found = routes.errors.not_found.or(errors.not_found.clone());
+            routes.errors.error = routes.errors.error.or(errors.error.clone());
+        }
+
+        Ok(routes)
+    }
+
+    /// Storage backend for the K/V namespaces that don't configure one
+    pub fn kv_backend(&self) -> KVBackend {
+        self.data
+            .as_ref()
+            .and_then(|data| data.kv.as_ref())
+            .map(|kv| kv.backend)
+            .unwrap_or_default()
+    }
+
     /// Provides a list of all file extensions handled by the runtimes
     /// that are currently installed in `project_root`
     pub fn get_runtime_extensions(&self, project_root: &Path) -> Vec<String> {
@@ -154,8 +207,233 @@ impl Default for Config {
         Self {
             version: 1,
             repositories: vec![new_repo],
+            data: None,
+            http_requests: None,
+            routes: None,
+            hosts: Vec::new(),
+        }
+    }
+}
+
+/// Default settings for the data connectors. Workers can override them
+/// in their own TOML files.
+///
+/// # Examples
+///
+/// ```toml
+/// [data.kv]
+/// backend = "sqlite"
+/// ```
+#[derive(Deserialize, Serialize, Default)]
+pub struct ConfigData {
+    /// Default settings for the Key / Value stores
+    pub kv: Option<ConfigKV>,
+}
+
+/// The storage backend of a K/V store. It can be configured for all the
+/// namespaces in the `.wws.toml` file or for a specific namespace in the
+/// worker TOML file.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KVBackend {
+    /// The data lives in memory and it's cleaned up on every restart
+    #[default]
+    Memory,
+    /// The data is saved in a JSON file per namespace in `.wws/kv`
+    Json,
+    /// The data is saved in a SQLite database in `.wws/kv`
+    Sqlite,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ConfigKV {
+    /// Storage backend for the K/V namespaces
+    #[serde(default)]
+    pub backend: KVBackend,
+}
+
+/// Network settings for the HTTP requests of the workers. Workers can
+/// override them in their own TOML files. Relative paths are resolved
+/// from the folder of the file that sets them.
+///
+/// # Examples
+///
+/// ```toml
+/// [http_requests]
+/// proxy = "http://proxy.example.com:3128"
+/// no_proxy = ["internal.example.com"]
+/// ca_certificates = ["certs/corporate-ca.pem"]
+///
+/// [http_requests.client_certificate]
+/// certificate = "certs/client.pem"
+/// key = "certs/client.key"
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ConfigHttpRequests {
+    /// Proxy for all the requests. It supports the `http` and `https`
+    /// schemes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Hosts, domains and IP ranges that don't use the proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<Vec<String>>,
+    /// PEM files with extra certificate authorities to trust
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_certificates: Option<Vec<PathBuf>>,
+    /// Client certificate for the servers that require mTLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_certificate: Option<ConfigClientCertificate>,
+}
+
+impl ConfigHttpRequests {
+    /// Take the settings that are not set from the given defaults
+    pub fn or(self, defaults: &Self) -> Self {
+        Self {
+            proxy: self.proxy.or_else(|| defaults.proxy.clone()),
+            no_proxy: self.no_proxy.or_else(|| defaults.no_proxy.clone()),
+            ca_certificates: self
+                .ca_certificates
+                .or_else(|| defaults.ca_certificates.clone()),
+            client_certificate: self
+                .client_certificate
+                .or_else(|| defaults.client_certificate.clone()),
         }
     }
+
+    /// Resolve the relative paths from the given folder
+    pub fn with_base_path(self, base: &Path) -> Self {
+        Self {
+            ca_certificates: self
+                .ca_certificates
+                .map(|paths| paths.iter().map(|path| base.join(path)).collect()),
+            client_certificate: self.client_certificate.map(|cert| ConfigClientCertificate {
+                certificate: base.join(cert.certificate),
+                key: base.join(cert.key),
+            }),
+            ..self
+        }
+    }
+}
+
+/// A PEM certificate and its PKCS #8 private key
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConfigClientCertificate {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Routes that don't come from the project files. The paths of the
+/// workers are relative to the project root. Paths may include
+/// parameters like the file names, such as `/users/[id]`.
+///
+/// # Examples
+///
+/// ```toml
+/// [[workers]]
+/// path = "/users/[id]"
+/// worker = "_handlers/user.js"
+/// methods = ["GET", "DELETE"]
+///
+/// [[redirects]]
+/// from = "/docs/[...page]"
+/// to = "https://docs.example.com/[...page]"
+/// permanent = true
+///
+/// [[rewrites]]
+/// from = "/blog/[slug]"
+/// to = "/posts/[slug]"
+///
+/// [errors]
+/// not_found = "_handlers/not-found.js"
+/// error = "_handlers/error.js"
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ConfigRoutes {
+    /// Paths served by the given workers
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub workers: Vec<ConfigRouteWorker>,
+    /// Paths that redirect the clients to a different location
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<ConfigRedirect>,
+    /// Paths that are served by the route of a different path
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rewrites: Vec<ConfigRewrite>,
+    /// Workers that reply to the requests without a route and
+    /// to the failed workers
+    #[serde(skip_serializing_if = "ConfigErrorWorkers::is_empty")]
+    pub errors: ConfigErrorWorkers,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigRouteWorker {
+    /// URL path of the route
+    pub path: String,
+    /// Worker file, relative to the project root
+    pub worker: PathBuf,
+    /// HTTP methods the route accepts. It accepts all of them by default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigRedirect {
+    /// URL path to redirect
+    pub from: String,
+    /// New location. It can be a path or a full URL
+    pub to: String,
+    /// Reply with a permanent redirect (308) instead of a temporary one (307)
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigRewrite {
+    /// URL path to rewrite
+    pub from: String,
+    /// URL path of the route that serves the request
+    pub to: String,
+}
+
+/// Workers that reply when the server cannot serve a request. The
+/// paths are relative to the project root.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ConfigErrorWorkers {
+    /// Reply to the requests that don't match any route or asset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_found: Option<PathBuf>,
+    /// Reply when a worker fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<PathBuf>,
+}
+
+impl ConfigErrorWorkers {
+    pub fn is_empty(&self) -> bool {
+        self.not_found.is_none() && self.error.is_none()
+    }
+}
+
+/// A project that serves the requests for a host name. The server
+/// replies to the rest of the requests with the main project.
+///
+/// # Examples
+///
+/// ```toml
+/// [[hosts]]
+/// host = "blog.example.com"
+/// root = "../blog"
+///
+/// [[hosts]]
+/// host = "*.shop.example.com"
+/// root = "./sites/shop"
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigHost {
+    /// Host name of the requests. A `*.` prefix matches any subdomain
+    pub host: String,
+    /// Root folder of the project, relative to the main project root
+    pub root: PathBuf,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -0,0 +1,317 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::{KVError, Result};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use wws_config::KVBackend;
+use wws_store::STORE_FOLDER;
+
+/// Folder inside the project store that keeps the persisted K/V stores
+const KV_FOLDER: &str = "kv";
+
+/// Name of the SQLite database. All the namespaces share the same file
+const SQLITE_FILENAME: &str = "kv.sqlite";
+
+/// The Key - Values of a namespace and their expiration
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct Entries {
+    /// The list of Key - Values
+    pub data: HashMap<String, String>,
+    /// Expiration of the keys that have a TTL. It's stored as seconds
+    /// since the Unix epoch, so it survives restarts
+    #[serde(default)]
+    pub expires_at: HashMap<String, u64>,
+}
+
+/// Format of the JSON snapshot files. The first version only stored
+/// the Key - Values
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotFile {
+    Entries(Entries),
+    Data(HashMap<String, String>),
+}
+
+/// Persist the content of a K/V store in the configured backend
+pub(crate) enum Storage {
+    Memory,
+    Json {
+        path: PathBuf,
+    },
+    Sqlite {
+        path: PathBuf,
+        namespace: String,
+        // The connection is not Sync, but the stores are shared
+        // between the server threads
+        connection: Mutex<Connection>,
+    },
+}
+
+impl Storage {
+    /// The backend that persists the data
+    pub fn backend(&self) -> KVBackend {
+        match self {
+            Self::Memory => KVBackend::Memory,
+            Self::Json { .. } => KVBackend::Json,
+            Self::Sqlite { .. } => KVBackend::Sqlite,
+        }
+    }
+
+    /// Initializes the storage of the given namespace. Backends that save
+    /// the data in a file create the folder in the project store.
+    pub fn open(project_root: &Path, namespace: &str, backend: KVBackend) -> Result<Self> {
+        if backend == KVBackend::Memory {
+            return Ok(Self::Memory);
+        }
+
+        let folder = project_root.join(STORE_FOLDER).join(KV_FOLDER);
+        fs::create_dir_all(&folder).map_err(|error| KVError::CannotCreateFolder {
+            path: folder.clone(),
+            error,
+        })?;
+
+        match backend {
+            KVBackend::Memory => Ok(Self::Memory),
+            KVBackend::Json => Ok(Self::Json {
+                path: folder.join(format!("{}.json", file_stem(namespace))),
+            }),
+            KVBackend::Sqlite => {
+                let path = folder.join(SQLITE_FILENAME);
+                let sqlite_error = |error| KVError::Sqlite {
+                    path: path.clone(),
+                    error,
+                };
+
+                let connection = Connection::open(&path).map_err(sqlite_error)?;
+                connection
+                    .execute_batch(
+                        "CREATE TABLE IF NOT EXISTS kv (
+                            namespace TEXT NOT NULL,
+                            key TEXT NOT NULL,
+                            value TEXT NOT NULL,
+                            expires_at INTEGER,
+                            PRIMARY KEY (namespace, key)
+                        )",
+                    )
+                    .map_err(sqlite_error)?;
+
+                // Databases created by previous versions don't have the expiration
+                let has_expiration = connection
+                    .prepare("SELECT 1 FROM pragma_table_info('kv') WHERE name = 'expires_at'")
+                    .and_then(|mut statement| statement.exists([]))
+                    .map_err(sqlite_error)?;
+                if !has_expiration {
+                    connection
+                        .execute_batch("ALTER TABLE kv ADD COLUMN expires_at INTEGER")
+                        .map_err(sqlite_error)?;
+                }
+
+                Ok(Self::Sqlite {
+                    path,
+                    namespace: namespace.to_string(),
+                    connection: Mutex::new(connection),
+                })
+            }
+        }
+    }
+
+    /// Read the data stored in the backend. Files that don't exist yet
+    /// are considered empty stores.
+    pub fn load(&self) -> Result<Entries> {
+        match self {
+            Self::Memory => Ok(Entries::default()),
+            Self::Json { path } => {
+                if !path.exists() {
+                    return Ok(Entries::default());
+                }
+
+                let content = fs::read(path).map_err(|error| KVError::SnapshotFile {
+                    path: path.clone(),
+                    error,
+                })?;
+
+                let snapshot =
+                    serde_json::from_slice(&content).map_err(|error| KVError::BadSnapshot {
+                        path: path.clone(),
+                        error,
+                    })?;
+
+                Ok(match snapshot {
+                    SnapshotFile::Entries(entries) => entries,
+                    SnapshotFile::Data(data) => Entries {
+                        data,
+                        ..Entries::default()
+                    },
+                })
+            }
+            Self::Sqlite {
+                path,
+                namespace,
+                connection,
+            } => {
+                let connection = connection.lock().expect("error locking the K/V database");
+                let sqlite_error = |error| KVError::Sqlite {
+                    path: path.clone(),
+                    error,
+                };
+
+                let mut statement = connection
+                    .prepare("SELECT key, value, expires_at FROM kv WHERE namespace = ?1")
+                    .map_err(sqlite_error)?;
+                let rows = statement
+                    .query_map(params![namespace], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })
+                    .map_err(sqlite_error)?;
+
+                let mut entries = Entries::default();
+                for row in rows {
+                    let (key, value, expires_at): (String, String, Option<u64>) =
+                        row.map_err(sqlite_error)?;
+
+                    if let Some(expires_at) = expires_at {
+                        entries.expires_at.insert(key.clone(), expires_at);
+                    }
+                    entries.data.insert(key, value);
+                }
+
+                Ok(entries)
+            }
+        }
+    }
+
+    /// Write the given entries into the backend, replacing the previous ones
+    pub fn save(&self, entries: &Entries) -> Result<()> {
+        match self {
+            Self::Memory => Ok(()),
+            Self::Json { path } => {
+                let content =
+                    serde_json::to_vec_pretty(entries).map_err(|error| KVError::BadSnapshot {
+                        path: path.clone(),
+                        error,
+                    })?;
+
+                // Write a temporary file first, so a crash in the middle
+                // doesn't leave a corrupted snapshot
+                let tmp_path = path.with_extension("json.tmp");
+                fs::write(&tmp_path, content)
+                    .and_then(|_| fs::rename(&tmp_path, path))
+                    .map_err(|error| KVError::SnapshotFile {
+                        path: path.clone(),
+                        error,
+                    })
+            }
+            Self::Sqlite {
+                path,
+                namespace,
+                connection,
+            } => {
+                let mut connection = connection.lock().expect("error locking the K/V database");
+                let sqlite_error = |error| KVError::Sqlite {
+                    path: path.clone(),
+                    error,
+                };
+
+                let transaction = connection.transaction().map_err(sqlite_error)?;
+                transaction
+                    .execute("DELETE FROM kv WHERE namespace = ?1", params![namespace])
+                    .map_err(sqlite_error)?;
+                {
+                    let mut statement = transaction
+                        .prepare(
+                            "INSERT INTO kv (namespace, key, value, expires_at) VALUES (?1, ?2, ?3, ?4)",
+                        )
+                        .map_err(sqlite_error)?;
+
+                    for (key, value) in &entries.data {
+                        statement
+                            .execute(params![namespace, key, value, entries.expires_at.get(key)])
+                            .map_err(sqlite_error)?;
+                    }
+                }
+
+                transaction.commit().map_err(sqlite_error)
+            }
+        }
+    }
+
+    /// Persist the changes of the given keys. The entries already include
+    /// the changes, so backends that save the whole namespace can use them.
+    pub fn save_keys(&self, entries: &Entries, keys: &[&str]) -> Result<()> {
+        match self {
+            Self::Memory | Self::Json { .. } => self.save(entries),
+            Self::Sqlite {
+                path,
+                namespace,
+                connection,
+            } => {
+                let mut connection = connection.lock().expect("error locking the K/V database");
+                let sqlite_error = |error| KVError::Sqlite {
+                    path: path.clone(),
+                    error,
+                };
+
+                let transaction = connection.transaction().map_err(sqlite_error)?;
+
+                for key in keys {
+                    match entries.data.get(*key) {
+                        Some(value) => transaction.execute(
+                            "INSERT OR REPLACE INTO kv (namespace, key, value, expires_at) VALUES (?1, ?2, ?3, ?4)",
+                            params![namespace, key, value, entries.expires_at.get(*key)],
+                        ),
+                        None => transaction.execute(
+                            "DELETE FROM kv WHERE namespace = ?1 AND key = ?2",
+                            params![namespace, key],
+                        ),
+                    }
+                    .map_err(sqlite_error)?;
+                }
+
+                transaction.commit().map_err(sqlite_error)
+            }
+        }
+    }
+}
+
+/// Build a file name from the namespace. Namespaces are defined by users,
+/// so any character that may escape the K/V folder is percent-encoded.
+/// Uppercase letters are encoded too, so every namespace gets a different
+/// file name even in case-insensitive file systems.
+fn file_stem(namespace: &str) -> String {
+    namespace
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_' {
+                char::from(byte).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_file_stems() {
+        assert_eq!(file_stem("cache-v1_users"), "cache-v1_users");
+        assert_eq!(file_stem("../etc"), "%2E%2E%2Fetc");
+
+        let namespaces = ["a.b", "a/b", "a_b", "a%2Eb", "a\\b", "añb", "A_b"];
+        let stems: std::collections::HashSet<String> = namespaces
+            .iter()
+            .map(|namespace| file_stem(namespace))
+            .collect();
+
+        assert_eq!(stems.len(), namespaces.len());
+    }
+}
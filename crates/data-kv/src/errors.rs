@@ -0,0 +1,88 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, KVError>;
+
+#[derive(Debug)]
+pub enum KVError {
+    /// The folder for the persisted stores couldn't be created
+    CannotCreateFolder {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The snapshot file couldn't be read or written
+    SnapshotFile {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The snapshot file is not valid JSON
+    BadSnapshot {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    /// Error when interacting with the SQLite database
+    Sqlite {
+        path: PathBuf,
+        error: rusqlite::Error,
+    },
+    /// Other requests changed the same keys since the worker got the snapshot
+    Conflict {
+        namespace: String,
+        keys: Vec<String>,
+    },
+    /// The change would exceed the maximum number of keys or bytes of the namespace
+    QuotaExceeded { namespace: String, limit: String },
+}
+
+impl std::fmt::Display for KVError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotCreateFolder { path, error } => {
+                write!(f, "Could not create folder {}: {}", path.display(), error)
+            }
+            Self::SnapshotFile { path, error } => {
+                write!(
+                    f,
+                    "Could not access the K/V snapshot {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            Self::BadSnapshot { path, error } => {
+                write!(
+                    f,
+                    "The K/V snapshot {} is not valid: {}",
+                    path.display(),
+                    error
+                )
+            }
+            Self::Sqlite { path, error } => {
+                write!(
+                    f,
+                    "Error accessing the K/V database {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            Self::Conflict { namespace, keys } => {
+                write!(
+                    f,
+                    "The keys {} of the K/V store {} were changed by another request",
+                    keys.join(", "),
+                    namespace
+                )
+            }
+            Self::QuotaExceeded { namespace, limit } => {
+                write!(
+                    f,
+                    "The K/V store {} exceeded its quota of {}",
+                    namespace, limit
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for KVError {}
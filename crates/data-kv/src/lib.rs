@@ -1,53 +1,186 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod backend;
+pub mod errors;
 mod store;
 
+use backend::Storage;
+use errors::Result;
 use serde::Deserialize;
 use std::collections::HashMap;
-use store::KVStore;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+pub use store::{KVConflictPolicy, KVSnapshot, KVStore};
+pub use wws_config::KVBackend;
+
+/// Interval between two checks for expired keys
+const PURGE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The K/V stores by namespace
+type Stores = RwLock<HashMap<String, Arc<KVStore>>>;
 
 /// The Key/Value store configuration. This information is read from workers TOML files.
 #[derive(Deserialize, Clone, Default)]
 pub struct KVConfigData {
     /// The namespace the worker will access in the global Key / Value store
     pub namespace: String,
+    /// The storage backend for this namespace. It overrides the default one
+    /// from the `.wws.toml` file
+    #[serde(default)]
+    pub backend: Option<KVBackend>,
+    /// How the worker accesses the store
+    #[serde(default)]
+    pub mode: KVMode,
+    /// How to write back the changes in snapshot mode when other requests
+    /// changed the same keys
+    #[serde(default)]
+    pub on_conflict: KVConflictPolicy,
+    /// Default time to live in seconds for the keys written by the worker
+    pub ttl_secs: Option<u64>,
+    /// Maximum number of keys in the namespace
+    pub max_keys: Option<usize>,
+    /// Maximum size in bytes of all the keys and values in the namespace
+    pub max_bytes: Option<usize>,
+}
+
+/// The way a worker accesses its K/V store
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KVMode {
+    /// The worker receives a snapshot of the namespace on every request
+    /// and returns the new state. The changes are written back when the
+    /// worker finishes
+    #[default]
+    Snapshot,
+    /// The worker reads and writes single keys through host functions
+    Host,
 }
 
-/// An in-memory Key/Value store. It contains multiple namespaces which has their
-/// own K/V store inside. This is used to scope the data workers can access
+/// A Key/Value store. It contains multiple namespaces which has their
+/// own K/V store inside. This is used to scope the data workers can access.
+/// Every namespace persists its data in the configured backend
 #[derive(Default)]
 pub struct KV {
-    /// The available K/V stores
-    pub stores: HashMap<String, KVStore>,
+    /// The available K/V stores. They are shared with the running workers
+    /// and the task that purges the expired keys
+    stores: Arc<Stores>,
+    /// The project root. Persisted stores are saved inside its `.wws` folder
+    project_root: PathBuf,
+    /// The backend for the namespaces that don't configure one
+    default_backend: KVBackend,
 }
 
 impl KV {
-    /// Creates a K/V store for the given namespace. If there's an existing store,
-    /// this method won't apply any change.
-    pub fn create_store(&mut self, namespace: &str) {
-        self.stores
-            .entry(namespace.to_string())
-            .or_insert_with(|| KVStore::new(namespace.to_string()));
+    /// Initializes an empty K/V for the given project. It starts a background
+    /// task to purge the expired keys, which finishes when the K/V is dropped
+    pub fn new(project_root: PathBuf, default_backend: KVBackend) -> Self {
+        let stores = Arc::new(Stores::default());
+        let weak_stores = Arc::downgrade(&stores);
+
+        if let Err(err) = thread::Builder::new()
+            .name("wws-kv-expiry".to_string())
+            .spawn(move || purge_expired_keys(weak_stores))
+        {
+            eprintln!("Error starting the task to purge the expired K/V keys: {err}");
+        }
+
+        Self {
+            stores,
+            project_root,
+            default_backend,
+        }
+    }
+
+    /// Creates a K/V store for the given namespace and loads its data from
+    /// the backend. If there's an existing store, this method won't apply any
+    /// change. It warns when the configuration doesn't match the existing
+    /// store. When a backend is not set, it uses the default one.
+    pub fn create_store(&self, config: &KVConfigData) -> Result<()> {
+        let mut stores = self
+            .stores
+            .write()
+            .expect("error locking the K/V stores for writing");
+        let backend = config.backend.unwrap_or(self.default_backend);
+
+        if let Some(store) = stores.get(&config.namespace) {
+            if !store.has_settings(config, backend) {
+                println!(
+                    "⚠️  The workers that access the K/V namespace {} have a different backend, TTL or quotas. The namespace keeps the ones from the first worker",
+                    config.namespace
+                );
+            }
+
+            return Ok(());
+        }
+
+        let storage = Storage::open(&self.project_root, &config.namespace, backend)?;
+        stores.insert(
+            config.namespace.clone(),
+            Arc::new(KVStore::new(config.namespace.clone(), storage, config)?),
+        );
+
+        Ok(())
     }
 
-    /// Replaces the content of an existing store. If the store doesn't exist,
-    /// this method won't apply any change
-    pub fn replace_store(&mut self, namespace: &str, state: &HashMap<String, String>) {
-        if let Some(store) = self.find_mut_store(namespace) {
-            store.replace(state.clone());
+    /// Replaces the content of an existing store and persists it. If the store
+    /// doesn't exist, this method won't apply any change
+    pub fn replace_store(&self, namespace: &str, state: &HashMap<String, String>) -> Result<()> {
+        if let Some(store) = self.find_store(namespace) {
+            store.replace(state.clone())?;
         }
+
+        Ok(())
     }
 
-    /// Look for the store related to the given namespace. This will return a reference
-    /// to the desired store if available
-    pub fn find_store(&self, namespace: &str) -> Option<&KVStore> {
-        self.stores.get(namespace)
+    /// Look for the store related to the given namespace. This will return a
+    /// handle to the desired store if available
+    pub fn find_store(&self, namespace: &str) -> Option<Arc<KVStore>> {
+        self.stores
+            .read()
+            .expect("error locking the K/V stores for reading")
+            .get(namespace)
+            .cloned()
     }
 
-    /// Similar to `find_store`, but it returns a mutable reference. It's useful
-    /// to write information to the given store
-    pub fn find_mut_store(&mut self, namespace: &str) -> Option<&mut KVStore> {
-        self.stores.get_mut(namespace)
+    /// List the namespaces of the available stores
+    pub fn namespaces(&self) -> Vec<String> {
+        self.stores
+            .read()
+            .expect("error locking the K/V stores for reading")
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Remove the expired keys from all the stores periodically. It stops
+/// when the stores are dropped
+fn purge_expired_keys(stores: Weak<Stores>) {
+    loop {
+        thread::sleep(PURGE_INTERVAL);
+
+        let Some(stores) = stores.upgrade() else {
+            return;
+        };
+
+        // Clone the handles, so the stores can be created while purging
+        let stores: Vec<Arc<KVStore>> = stores
+            .read()
+            .expect("error locking the K/V stores for reading")
+            .values()
+            .cloned()
+            .collect();
+
+        for store in stores {
+            if let Err(err) = store.purge_expired() {
+                eprintln!(
+                    "Error purging the expired keys of the K/V store {}: {err}",
+                    store.namespace
+                );
+            }
+        }
     }
 }
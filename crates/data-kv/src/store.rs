@@ -1,33 +1,756 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::HashMap;
+use crate::backend::{Entries, Storage};
+use crate::errors::{KVError, Result};
+use crate::KVConfigData;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wws_config::KVBackend;
 
-/// A K/V store. It's composed by a namespace and the list of Key/Values
+/// How to write back the state returned by a worker in snapshot mode when
+/// other requests changed the same keys since it got the snapshot.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KVConflictPolicy {
+    /// The changes of the worker override the ones from other requests
+    #[default]
+    LastWriteWins,
+    /// Only the keys that didn't change since the snapshot are written.
+    /// The conflicting keys keep the value from the other requests
+    Merge,
+    /// Nothing is written if any of the changed keys has a conflict.
+    /// The server replies with a 409 status code
+    Reject,
+}
+
+/// A copy of the K/V store content. It includes the version of the store,
+/// so the changes can be compared with the ones from other requests.
+pub struct KVSnapshot {
+    /// The list of Key - Values
+    pub data: HashMap<String, String>,
+    /// Version of the store when the snapshot was taken
+    pub version: u64,
+    /// Keeps the revisions the snapshot needs while it's in use
+    _lease: SnapshotLease,
+}
+
+/// The number of snapshots in use by version of the store
+type Leases = Arc<Mutex<BTreeMap<u64, usize>>>;
+
+/// Registers a snapshot in use. The store only keeps the revisions that
+/// are newer than the oldest snapshot in use
+struct SnapshotLease {
+    leases: Leases,
+    version: u64,
+}
+
+impl SnapshotLease {
+    fn new(leases: &Leases, version: u64) -> Self {
+        *leases
+            .lock()
+            .expect("error locking the K/V snapshots")
+            .entry(version)
+            .or_default() += 1;
+
+        Self {
+            leases: Arc::clone(leases),
+            version,
+        }
+    }
+}
+
+impl Drop for SnapshotLease {
+    fn drop(&mut self) {
+        let mut leases = self.leases.lock().expect("error locking the K/V snapshots");
+
+        if let Some(count) = leases.get_mut(&self.version) {
+            *count -= 1;
+            if *count == 0 {
+                leases.remove(&self.version);
+            }
+        }
+    }
+}
+
+/// The content of a K/V store and the information to detect conflicts
+#[derive(Default)]
+struct State {
+    /// The list of Key - Values and their expiration
+    entries: Entries,
+    /// Size in bytes of all the keys and values
+    bytes: usize,
+    /// Version of the last change of every key. Deleted keys keep their
+    /// revision, so removing a key can be detected as a conflict too.
+    /// Only the revisions newer than the oldest snapshot in use are kept
+    revisions: HashMap<String, u64>,
+    /// Version of the store. It increases on every change
+    version: u64,
+}
+
+impl State {
+    /// Check if the given key expired
+    fn is_expired(&self, key: &str, now: u64) -> bool {
+        self.entries
+            .expires_at
+            .get(key)
+            .is_some_and(|expires_at| *expires_at <= now)
+    }
+
+    /// Iterate over the Key - Values that didn't expire
+    fn live_entries(&self) -> impl Iterator<Item = (&String, &String)> {
+        let now = now_secs();
+        self.entries
+            .data
+            .iter()
+            .filter(move |(key, _)| !self.is_expired(key, now))
+    }
+}
+
+/// A change in a single key
+struct Change {
+    key: String,
+    /// The new value. `None` removes the key
+    value: Option<String>,
+    /// Expiration of the new value as seconds since the Unix epoch
+    expires_at: Option<u64>,
+}
+
+/// A K/V store. It's composed by a namespace and the list of Key/Values.
+/// The store is shared between the requests that access the namespace,
+/// so every operation locks the Key/Values
 pub struct KVStore {
     /// The namespace associated to this Key/Value store
     pub namespace: String,
     /// The list of Key - Values. In this project, both keys and values are considered
     /// strings.
-    store: HashMap<String, String>,
+    store: RwLock<State>,
+    /// The snapshots in use. They decide which revisions must be kept
+    leases: Leases,
+    /// The backend that persists the Key - Values
+    storage: Storage,
+    /// Default time to live in seconds for the keys written by workers
+    ttl_secs: Option<u64>,
+    /// Maximum number of keys in the namespace
+    max_keys: Option<usize>,
+    /// Maximum size in bytes of all the keys and values in the namespace
+    max_bytes: Option<usize>,
 }
 
 impl KVStore {
-    /// Creates a new K/V store for the given namespace
-    pub fn new(namespace: String) -> Self {
-        Self {
+    /// Creates a new K/V store for the given namespace. It loads the
+    /// previous content from the storage backend. The limits come from
+    /// the configuration of the first worker that accesses the namespace
+    pub(crate) fn new(namespace: String, storage: Storage, config: &KVConfigData) -> Result<Self> {
+        let entries = storage.load()?;
+
+        Ok(Self {
             namespace,
-            store: HashMap::new(),
-        }
+            store: RwLock::new(State {
+                bytes: size_of(&entries.data),
+                entries,
+                ..State::default()
+            }),
+            leases: Leases::default(),
+            storage,
+            ttl_secs: config.ttl_secs,
+            max_keys: config.max_keys,
+            max_bytes: config.max_bytes,
+        })
+    }
+
+    /// Check if the store uses the given backend and the settings of the
+    /// given configuration
+    pub(crate) fn has_settings(&self, config: &KVConfigData, backend: KVBackend) -> bool {
+        self.storage.backend() == backend
+            && self.ttl_secs == config.ttl_secs
+            && self.max_keys == config.max_keys
+            && self.max_bytes == config.max_bytes
     }
 
     /// Clone the current content of the Key/Value store
     pub fn clone(&self) -> HashMap<String, String> {
-        self.store.clone()
+        self.snapshot().data
+    }
+
+    /// Clone the current content of the Key/Value store with its version.
+    /// Expired keys are not included
+    pub fn snapshot(&self) -> KVSnapshot {
+        let store = self
+            .store
+            .read()
+            .expect("error locking the K/V store for reading");
+
+        KVSnapshot {
+            data: store
+                .live_entries()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            version: store.version,
+            _lease: SnapshotLease::new(&self.leases, store.version),
+        }
+    }
+
+    /// Replace the content of the K/V store with a new state and persist it
+    pub fn replace(&self, state: HashMap<String, String>) -> Result<()> {
+        let mut store = self
+            .store
+            .write()
+            .expect("error locking the K/V store for writing");
+
+        // Like the rest of changes, a new state over the quota is only
+        // rejected when it grows the namespace
+        let bytes = size_of(&state);
+        self.check_quota(state.len(), bytes, store.entries.data.len(), store.bytes)?;
+
+        let expires_at = self.default_expiration();
+        let entries = Entries {
+            expires_at: state
+                .keys()
+                .filter_map(|key| Some((key.clone(), expires_at?)))
+                .collect(),
+            data: state,
+        };
+        self.storage.save(&entries)?;
+
+        let version = store.version + 1;
+        let keys: Vec<String> = store
+            .entries
+            .data
+            .keys()
+            .chain(entries.data.keys())
+            .cloned()
+            .collect();
+        for key in keys {
+            store.revisions.insert(key, version);
+        }
+        store.entries = entries;
+        store.bytes = bytes;
+        store.version = version;
+        self.prune_revisions(&mut store);
+
+        Ok(())
+    }
+
+    /// Write the state returned by a worker that received the given snapshot.
+    /// Only the keys the worker changed are written. The policy decides what
+    /// to do with the keys that other requests changed after the snapshot.
+    /// It returns the conflicting keys.
+    pub fn write_back(
+        &self,
+        snapshot: &KVSnapshot,
+        state: &HashMap<String, String>,
+        policy: KVConflictPolicy,
+    ) -> Result<Vec<String>> {
+        let mut store = self
+            .store
+            .write()
+            .expect("error locking the K/V store for writing");
+
+        let expires_at = self.default_expiration();
+        let mut changes: Vec<Change> = state
+            .iter()
+            .filter(|(key, value)| snapshot.data.get(*key) != Some(*value))
+            .map(|(key, value)| Change {
+                key: key.clone(),
+                value: Some(value.clone()),
+                expires_at,
+            })
+            .chain(
+                snapshot
+                    .data
+                    .keys()
+                    .filter(|key| !state.contains_key(*key))
+                    .map(|key| Change {
+                        key: key.clone(),
+                        value: None,
+                        expires_at: None,
+                    }),
+            )
+            .collect();
+
+        let conflicts: Vec<String> = changes
+            .iter()
+            .filter(|change| {
+                store
+                    .revisions
+                    .get(&change.key)
+                    .is_some_and(|revision| *revision > snapshot.version)
+            })
+            .map(|change| change.key.clone())
+            .collect();
+
+        match policy {
+            KVConflictPolicy::LastWriteWins => {}
+            KVConflictPolicy::Merge => changes.retain(|change| !conflicts.contains(&change.key)),
+            KVConflictPolicy::Reject if !conflicts.is_empty() => {
+                return Err(KVError::Conflict {
+                    namespace: self.namespace.clone(),
+                    keys: conflicts,
+                });
+            }
+            KVConflictPolicy::Reject => {}
+        }
+
+        self.apply(&mut store, changes)?;
+
+        Ok(conflicts)
+    }
+
+    /// Read the value of the given key. Expired keys are not returned
+    pub fn get(&self, key: &str) -> Option<String> {
+        let store = self
+            .store
+            .read()
+            .expect("error locking the K/V store for reading");
+
+        if store.is_expired(key, now_secs()) {
+            return None;
+        }
+
+        store.entries.data.get(key).cloned()
+    }
+
+    /// Set the value of the given key and persist it. It uses the default
+    /// time to live of the namespace. The change is reverted if the backend
+    /// cannot save it
+    pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.set_with_expiration(key, value, self.default_expiration())
+    }
+
+    /// Set the value of the given key with a time to live in seconds
+    pub fn set_with_ttl(&self, key: &str, value: &str, ttl_secs: u64) -> Result<()> {
+        self.set_with_expiration(key, value, Some(now_secs().saturating_add(ttl_secs)))
+    }
+
+    /// Remove the given key and persist the change. The change is
+    /// reverted if the backend cannot save it
+    pub fn delete(&self, key: &str) -> Result<()> {
+        let mut store = self
+            .store
+            .write()
+            .expect("error locking the K/V store for writing");
+
+        self.apply(
+            &mut store,
+            vec![Change {
+                key: key.to_string(),
+                value: None,
+                expires_at: None,
+            }],
+        )
+    }
+
+    /// List all the keys in the store. Expired keys are not included
+    pub fn keys(&self) -> Vec<String> {
+        self.store
+            .read()
+            .expect("error locking the K/V store for reading")
+            .live_entries()
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Remove the expired keys from the store and the backend. It
+    /// returns the number of removed keys. Expired keys are already absent
+    /// for the requests, so removing them doesn't record a revision and
+    /// cannot cause a conflict
+    pub fn purge_expired(&self) -> Result<usize> {
+        let mut store = self
+            .store
+            .write()
+            .expect("error locking the K/V store for writing");
+
+        let now = now_secs();
+        let changes: Vec<Change> = store
+            .entries
+            .expires_at
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(key, _)| Change {
+                key: key.clone(),
+                value: None,
+                expires_at: None,
+            })
+            .collect();
+        let purged = changes.len();
+
+        self.update(&mut store, changes)?;
+
+        Ok(purged)
+    }
+
+    /// Set a key with the given expiration
+    fn set_with_expiration(&self, key: &str, value: &str, expires_at: Option<u64>) -> Result<()> {
+        let mut store = self
+            .store
+            .write()
+            .expect("error locking the K/V store for writing");
+
+        self.apply(
+            &mut store,
+            vec![Change {
+                key: key.to_string(),
+                value: Some(value.to_string()),
+                expires_at,
+            }],
+        )
+    }
+
+    /// Expiration for the keys written without an explicit time to live
+    fn default_expiration(&self) -> Option<u64> {
+        self.ttl_secs
+            .map(|ttl_secs| now_secs().saturating_add(ttl_secs))
+    }
+
+    /// Check the quotas of the namespace. Changes that reduce the usage
+    /// are always allowed, so a namespace over its quota can be cleaned up
+    fn check_quota(
+        &self,
+        keys: usize,
+        bytes: usize,
+        prev_keys: usize,
+        prev_bytes: usize,
+    ) -> Result<()> {
+        if let Some(max_keys) = self.max_keys {
+            if keys > max_keys && keys > prev_keys {
+                return Err(KVError::QuotaExceeded {
+                    namespace: self.namespace.clone(),
+                    limit: format!("{max_keys} keys"),
+                });
+            }
+        }
+
+        if let Some(max_bytes) = self.max_bytes {
+            if bytes > max_bytes && bytes > prev_bytes {
+                return Err(KVError::QuotaExceeded {
+                    namespace: self.namespace.clone(),
+                    limit: format!("{max_bytes} bytes"),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the given changes, persist them and record their revisions
+    fn apply(&self, store: &mut State, changes: Vec<Change>) -> Result<()> {
+        let keys = self.update(store, changes)?;
+
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        store.version += 1;
+        for key in keys {
+            store.revisions.insert(key, store.version);
+        }
+        self.prune_revisions(store);
+
+        Ok(())
+    }
+
+    /// Apply the given changes and persist them. The changes are reverted
+    /// if they exceed the quotas or the backend cannot save them. It returns
+    /// the changed keys
+    fn update(&self, store: &mut State, changes: Vec<Change>) -> Result<Vec<String>> {
+        if changes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (prev_keys, prev_bytes) = (store.entries.data.len(), store.bytes);
+        let mut previous = Vec::with_capacity(changes.len());
+
+        for change in changes {
+            let old_value = match change.value {
+                Some(value) => {
+                    store.bytes += change.key.len() + value.len();
+                    store.entries.data.insert(change.key.clone(), value)
+                }
+                None => store.entries.data.remove(&change.key),
+            };
+            if let Some(old_value) = &old_value {
+                store.bytes -= change.key.len() + old_value.len();
+            }
+
+            let old_expires_at = match change.expires_at {
+                Some(expires_at) => store
+                    .entries
+                    .expires_at
+                    .insert(change.key.clone(), expires_at),
+                None => store.entries.expires_at.remove(&change.key),
+            };
+
+            previous.push((change.key, old_value, old_expires_at));
+        }
+
+        let keys: Vec<&str> = previous.iter().map(|(key, _, _)| key.as_str()).collect();
+        let result = self
+            .check_quota(store.entries.data.len(), store.bytes, prev_keys, prev_bytes)
+            .and_then(|_| self.storage.save_keys(&store.entries, &keys));
+
+        if let Err(err) = result {
+            // Restore in reverse order, in case a key was changed twice
+            for (key, old_value, old_expires_at) in previous.into_iter().rev() {
+                match old_value {
+                    Some(value) => store.entries.data.insert(key.clone(), value),
+                    None => store.entries.data.remove(&key),
+                };
+                match old_expires_at {
+                    Some(expires_at) => store.entries.expires_at.insert(key, expires_at),
+                    None => store.entries.expires_at.remove(&key),
+                };
+            }
+            store.bytes = prev_bytes;
+
+            return Err(err);
+        }
+
+        Ok(previous.into_iter().map(|(key, _, _)| key).collect())
+    }
+
+    /// Remove the revisions that cannot cause a conflict. A snapshot only
+    /// conflicts with the revisions newer than its version, so the ones up
+    /// to the oldest snapshot in use are not required anymore
+    fn prune_revisions(&self, store: &mut State) {
+        let oldest = self
+            .leases
+            .lock()
+            .expect("error locking the K/V snapshots")
+            .keys()
+            .next()
+            .copied()
+            .unwrap_or(store.version);
+
+        store.revisions.retain(|_, revision| *revision > oldest);
+    }
+}
+
+/// Size in bytes of the given Key - Values
+fn size_of(data: &HashMap<String, String>) -> usize {
+    data.iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum()
+}
+
+/// Current time as seconds since the Unix epoch
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_with(data: &[(&str, &str)]) -> KVStore {
+        let store = KVStore::new(
+            "test".to_string(),
+            Storage::Memory,
+            &KVConfigData::default(),
+        )
+        .unwrap();
+        for (key, value) in data {
+            store.set(key, value).unwrap();
+        }
+
+        store
+    }
+
+    fn state(data: &[(&str, &str)]) -> HashMap<String, String> {
+        data.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
     }
 
-    /// Replace the content of the K/V store with a new state
-    pub fn replace(&mut self, state: HashMap<String, String>) {
-        self.store = state;
+    #[test]
+    fn has_settings_compares_backend_ttl_and_quotas() {
+        let store = store_with(&[]);
+        let config = KVConfigData::default();
+
+        assert!(store.has_settings(&config, KVBackend::Memory));
+        assert!(!store.has_settings(&config, KVBackend::Json));
+        assert!(!store.has_settings(
+            &KVConfigData {
+                max_keys: Some(10),
+                ..KVConfigData::default()
+            },
+            KVBackend::Memory
+        ));
+    }
+
+    #[test]
+    fn write_back_keeps_changes_from_other_requests() {
+        let store = store_with(&[("a", "1"), ("b", "1")]);
+        let first = store.snapshot();
+        let second = store.snapshot();
+
+        // Each request changes a different key
+        store
+            .write_back(
+                &first,
+                &state(&[("a", "2"), ("b", "1")]),
+                KVConflictPolicy::Reject,
+            )
+            .unwrap();
+        store
+            .write_back(
+                &second,
+                &state(&[("a", "1"), ("b", "2")]),
+                KVConflictPolicy::Reject,
+            )
+            .unwrap();
+
+        assert_eq!(store.clone(), state(&[("a", "2"), ("b", "2")]));
+    }
+
+    #[test]
+    fn write_back_conflict_policies() {
+        let tests = [
+            (KVConflictPolicy::LastWriteWins, "3", true),
+            (KVConflictPolicy::Merge, "2", true),
+            (KVConflictPolicy::Reject, "2", false),
+        ];
+
+        for (policy, expected, success) in tests {
+            let store = store_with(&[("a", "1")]);
+            let first = store.snapshot();
+            let second = store.snapshot();
+
+            store
+                .write_back(&first, &state(&[("a", "2")]), policy)
+                .unwrap();
+            let result = store.write_back(&second, &state(&[("a", "3"), ("b", "1")]), policy);
+
+            assert_eq!(result.is_ok(), success);
+            assert_eq!(store.get("a").unwrap(), expected);
+            // Keys without conflicts are written unless the policy rejects the changes
+            assert_eq!(store.get("b").is_some(), success);
+        }
+    }
+
+    #[test]
+    fn write_back_detects_deleted_keys() {
+        let store = store_with(&[("a", "1")]);
+        let first = store.snapshot();
+        let second = store.snapshot();
+
+        store
+            .write_back(&first, &HashMap::new(), KVConflictPolicy::Reject)
+            .unwrap();
+        let result = store.write_back(&second, &state(&[("a", "2")]), KVConflictPolicy::Reject);
+
+        assert!(matches!(result, Err(KVError::Conflict { .. })));
+        assert!(store.get("a").is_none());
+    }
+
+    #[test]
+    fn revisions_are_pruned_when_snapshots_finish() {
+        let store = store_with(&[("a", "1"), ("b", "1")]);
+        let snapshot = store.snapshot();
+
+        store.delete("a").unwrap();
+        store.set("b", "2").unwrap();
+        assert_eq!(store.store.read().unwrap().revisions.len(), 2);
+
+        drop(snapshot);
+        store.set("c", "1").unwrap();
+        assert!(store.store.read().unwrap().revisions.is_empty());
+
+        // A snapshot in use still detects the conflicts
+        let snapshot = store.snapshot();
+        store.set("c", "2").unwrap();
+        let result = store.write_back(&snapshot, &state(&[("c", "3")]), KVConflictPolicy::Reject);
+        assert!(matches!(result, Err(KVError::Conflict { .. })));
+    }
+
+    #[test]
+    fn quotas_reject_changes_that_grow_the_namespace() {
+        let config = KVConfigData {
+            max_keys: Some(2),
+            max_bytes: Some(10),
+            ..KVConfigData::default()
+        };
+        let store = KVStore::new("test".to_string(), Storage::Memory, &config).unwrap();
+
+        store.set("a", "1").unwrap();
+        store.set("b", "1").unwrap();
+        assert!(matches!(
+            store.set("c", "1"),
+            Err(KVError::QuotaExceeded { .. })
+        ));
+        assert!(matches!(
+            store.set("a", "too long"),
+            Err(KVError::QuotaExceeded { .. })
+        ));
+
+        // Failed changes are reverted
+        assert_eq!(store.clone(), state(&[("a", "1"), ("b", "1")]));
+
+        store.delete("a").unwrap();
+        store.set("c", "1").unwrap();
+    }
+
+    #[test]
+    fn replace_allows_shrinking_a_namespace_over_quota() {
+        let config = KVConfigData {
+            max_keys: Some(1),
+            ..KVConfigData::default()
+        };
+        let store = KVStore::new("test".to_string(), Storage::Memory, &config).unwrap();
+
+        // The namespace was saved before the quota was configured
+        {
+            let mut current = store.store.write().unwrap();
+            current.entries.data = state(&[("a", "1"), ("b", "1"), ("c", "1")]);
+            current.bytes = size_of(&current.entries.data);
+        }
+
+        store.replace(state(&[("a", "1"), ("b", "1")])).unwrap();
+        assert!(matches!(
+            store.replace(state(&[("a", "1"), ("b", "1"), ("c", "1")])),
+            Err(KVError::QuotaExceeded { .. })
+        ));
+        assert_eq!(store.clone(), state(&[("a", "1"), ("b", "1")]));
+    }
+
+    #[test]
+    fn expired_keys_are_hidden_and_purged() {
+        let store = store_with(&[("a", "1")]);
+
+        store.set_with_ttl("b", "1", 0).unwrap();
+        store.set_with_ttl("c", "1", 3600).unwrap();
+
+        assert!(store.get("b").is_none());
+        assert_eq!(store.clone(), state(&[("a", "1"), ("c", "1")]));
+        assert_eq!(store.purge_expired().unwrap(), 1);
+    }
+
+    #[test]
+    fn purging_keys_does_not_cause_conflicts() {
+        let store = store_with(&[("a", "1"), ("b", "1")]);
+        let snapshot = store.snapshot();
+
+        // The key expires while the request runs
+        store
+            .store
+            .write()
+            .unwrap()
+            .entries
+            .expires_at
+            .insert("a".to_string(), 0);
+        assert_eq!(store.purge_expired().unwrap(), 1);
+
+        let result = store.write_back(
+            &snapshot,
+            &state(&[("a", "2"), ("b", "2")]),
+            KVConflictPolicy::Reject,
+        );
+        assert!(result.unwrap().is_empty());
+        assert_eq!(store.clone(), state(&[("a", "2"), ("b", "2")]));
     }
 }
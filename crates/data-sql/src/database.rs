@@ -0,0 +1,391 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::{Result, SQLError};
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    limits::Limit,
+    params_from_iter,
+    types::{ToSqlOutput, ValueRef},
+    Connection, ToSql,
+};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time a statement waits for other connections to release the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a statement can run before it's interrupted
+const STATEMENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of SQLite virtual machine instructions between the checks
+/// of the statement deadline
+const PROGRESS_STEPS: i32 = 1000;
+
+/// Pragmas that only read information, although they receive an argument
+const READ_ONLY_PRAGMAS: [&str; 10] = [
+    "foreign_key_check",
+    "foreign_key_list",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "quick_check",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+];
+
+/// A value in a row or a parameter of a statement. They map to the
+/// SQLite storage classes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SQLValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl ToSql for SQLValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self {
+            Self::Null => ValueRef::Null,
+            Self::Integer(value) => ValueRef::Integer(*value),
+            Self::Real(value) => ValueRef::Real(*value),
+            Self::Text(value) => ValueRef::Text(value.as_bytes()),
+            Self::Blob(value) => ValueRef::Blob(value),
+        };
+
+        Ok(ToSqlOutput::Borrowed(value))
+    }
+}
+
+impl From<ValueRef<'_>> for SQLValue {
+    fn from(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Null => Self::Null,
+            ValueRef::Integer(value) => Self::Integer(value),
+            ValueRef::Real(value) => Self::Real(value),
+            ValueRef::Text(value) => Self::Text(String::from_utf8_lossy(value).into_owned()),
+            ValueRef::Blob(value) => Self::Blob(value.to_vec()),
+        }
+    }
+}
+
+/// The result of a query
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SQLRows {
+    /// Name of the columns
+    pub columns: Vec<String>,
+    /// Values of every row, in the same order as the columns
+    pub rows: Vec<Vec<SQLValue>>,
+}
+
+/// Interrupts the running statements of a request. Workers can stop
+/// waiting for a statement, like when they exceed their timeout.
+/// Cancelling it releases the database for other requests
+#[derive(Clone, Debug, Default)]
+pub struct SQLCancellation(Arc<AtomicBool>);
+
+impl SQLCancellation {
+    /// Interrupt the statement at the next deadline check
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A SQLite database of the project
+pub struct SQLDatabase {
+    /// The database name
+    pub name: String,
+    // The connection is not Sync, but the databases are shared
+    // between the server threads
+    connection: Mutex<Connection>,
+}
+
+impl SQLDatabase {
+    /// Open the database in the given file. It's created if it doesn't exist.
+    /// Workers can only access this file, so the connection cannot attach
+    /// other databases or change the pragmas
+    pub(crate) fn open(name: &str, path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .and_then(|connection| {
+                connection.busy_timeout(BUSY_TIMEOUT)?;
+                // It also blocks `VACUUM INTO`, which attaches the new file
+                connection.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0);
+                connection.authorizer(Some(authorize));
+                Ok(connection)
+            })
+            .map_err(|error| SQLError::Sqlite {
+                path: path.to_path_buf(),
+                error,
+            })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Run a statement that returns rows, like `SELECT`
+    pub fn query(
+        &self,
+        statement: &str,
+        params: &[SQLValue],
+        cancellation: &SQLCancellation,
+    ) -> Result<SQLRows> {
+        self.run(cancellation, |connection| {
+            run_query(connection, statement, params)
+        })
+    }
+
+    /// Run a statement that changes the database, like `INSERT` or
+    /// `CREATE TABLE`. It returns the number of changed rows
+    pub fn execute(
+        &self,
+        statement: &str,
+        params: &[SQLValue],
+        cancellation: &SQLCancellation,
+    ) -> Result<u64> {
+        self.run(cancellation, |connection| {
+            connection
+                .execute(statement, params_from_iter(params))
+                .map(|changes| changes as u64)
+        })
+    }
+
+    /// Run a statement until it finishes, it's cancelled or it reaches the
+    /// deadline. The connection is shared, so a slow statement blocks the
+    /// rest of requests
+    fn run<T, F>(&self, cancellation: &SQLCancellation, run: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<T>,
+    {
+        let connection = self
+            .connection
+            .lock()
+            .expect("error locking the SQL database");
+
+        // SQLite interrupts the statement when the handler returns true
+        let deadline = Instant::now() + STATEMENT_TIMEOUT;
+        let handler_cancellation = cancellation.clone();
+        connection.progress_handler(
+            PROGRESS_STEPS,
+            Some(move || handler_cancellation.is_cancelled() || Instant::now() > deadline),
+        );
+
+        let result = run(&connection);
+        connection.progress_handler(PROGRESS_STEPS, None::<fn() -> bool>);
+
+        result.map_err(|error| match error {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: rusqlite::ErrorCode::OperationInterrupted,
+                    ..
+                },
+                _,
+            ) => SQLError::Interrupted {
+                database: self.name.clone(),
+            },
+            error => SQLError::Statement {
+                database: self.name.clone(),
+                error,
+            },
+        })
+    }
+}
+
+/// Deny the statements that access other files or change the connection
+/// settings. SQLite calls it when it prepares a statement
+fn authorize(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Attach { .. } => Authorization::Deny,
+        AuthAction::Pragma {
+            pragma_name,
+            pragma_value: Some(_),
+        } if !READ_ONLY_PRAGMAS.contains(&pragma_name.to_lowercase().as_str()) => {
+            Authorization::Deny
+        }
+        _ => Authorization::Allow,
+    }
+}
+
+/// Prepare the statement and collect all the rows it returns
+fn run_query(
+    connection: &Connection,
+    statement: &str,
+    params: &[SQLValue],
+) -> rusqlite::Result<SQLRows> {
+    let mut statement = connection.prepare(statement)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut cursor = statement.query(params_from_iter(params))?;
+
+    while let Some(row) = cursor.next()? {
+        rows.push(
+            (0..columns.len())
+                .map(|index| row.get_ref(index).map(SQLValue::from))
+                .collect::<rusqlite::Result<_>>()?,
+        );
+    }
+
+    Ok(SQLRows { columns, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> SQLDatabase {
+        let database = SQLDatabase::open("test", Path::new(":memory:")).unwrap();
+        database
+            .execute(
+                "CREATE TABLE todos (id INTEGER PRIMARY KEY, title TEXT, done REAL)",
+                &[],
+                &SQLCancellation::default(),
+            )
+            .unwrap();
+
+        database
+    }
+
+    #[test]
+    fn execute_returns_the_changed_rows() {
+        let database = database();
+
+        let changes = database
+            .execute(
+                "INSERT INTO todos (title, done) VALUES (?, ?), (?, ?)",
+                &[
+                    SQLValue::Text("first".to_string()),
+                    SQLValue::Real(0.5),
+                    SQLValue::Text("second".to_string()),
+                    SQLValue::Null,
+                ],
+                &SQLCancellation::default(),
+            )
+            .unwrap();
+
+        assert_eq!(changes, 2);
+    }
+
+    #[test]
+    fn query_returns_columns_and_values() {
+        let database = database();
+        database
+            .execute(
+                "INSERT INTO todos (title, done) VALUES (?, ?)",
+                &[SQLValue::Text("first".to_string()), SQLValue::Real(0.5)],
+                &SQLCancellation::default(),
+            )
+            .unwrap();
+
+        let rows = database
+            .query(
+                "SELECT id, title, done FROM todos WHERE title = ?",
+                &[SQLValue::Text("first".to_string())],
+                &SQLCancellation::default(),
+            )
+            .unwrap();
+
+        assert_eq!(rows.columns, vec!["id", "title", "done"]);
+        assert_eq!(
+            rows.rows,
+            vec![vec![
+                SQLValue::Integer(1),
+                SQLValue::Text("first".to_string()),
+                SQLValue::Real(0.5)
+            ]]
+        );
+    }
+
+    #[test]
+    fn invalid_statements_fail() {
+        let database = database();
+
+        assert!(matches!(
+            database.query("SELECT * FROM missing", &[], &SQLCancellation::default()),
+            Err(SQLError::Statement { .. })
+        ));
+        assert!(matches!(
+            database.execute(
+                "INSERT INTO todos (title) VALUES (?)",
+                &[],
+                &SQLCancellation::default()
+            ),
+            Err(SQLError::Statement { .. })
+        ));
+    }
+
+    #[test]
+    fn statements_cannot_access_other_files() {
+        let database = database();
+
+        for statement in [
+            "ATTACH DATABASE ':memory:' AS other",
+            "VACUUM INTO 'copy.db'",
+            "PRAGMA foreign_keys = OFF",
+        ] {
+            assert!(
+                matches!(
+                    database.execute(statement, &[], &SQLCancellation::default()),
+                    Err(SQLError::Statement { .. })
+                ),
+                "{statement} should fail"
+            );
+        }
+        assert!(!Path::new("copy.db").exists());
+
+        // Pragmas that read information are allowed
+        let rows = database
+            .query("PRAGMA table_info(todos)", &[], &SQLCancellation::default())
+            .unwrap();
+        assert_eq!(rows.rows.len(), 3);
+    }
+
+    #[test]
+    fn cancelled_statements_release_the_database() {
+        let database = database();
+        let cancellation = SQLCancellation::default();
+
+        let request = cancellation.clone();
+        let cancel = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            request.cancel();
+        });
+
+        // It never ends unless SQLite interrupts it
+        let result = database.query(
+            "WITH RECURSIVE counter(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM counter) SELECT count(*) FROM counter",
+            &[],
+            &cancellation,
+        );
+        cancel.join().unwrap();
+        assert!(matches!(result, Err(SQLError::Interrupted { .. })));
+
+        let rows = database
+            .query(
+                "SELECT count(*) FROM todos",
+                &[],
+                &SQLCancellation::default(),
+            )
+            .unwrap();
+        assert_eq!(rows.rows, vec![vec![SQLValue::Integer(0)]]);
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, SQLError>;
+
+#[derive(Debug)]
+pub enum SQLError {
+    /// The folder for the databases couldn't be created
+    CannotCreateFolder {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The database name can only contain alphanumeric characters, `-` and `_`
+    InvalidName { database: String },
+    /// The database file couldn't be opened
+    Sqlite {
+        path: PathBuf,
+        error: rusqlite::Error,
+    },
+    /// The statement was interrupted because the request stopped waiting
+    /// for it or it exceeded the time limit
+    Interrupted { database: String },
+    /// The statement couldn't be prepared or run
+    Statement {
+        database: String,
+        error: rusqlite::Error,
+    },
+}
+
+impl std::fmt::Display for SQLError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotCreateFolder { path, error } => {
+                write!(f, "Could not create folder {}: {}", path.display(), error)
+            }
+            Self::InvalidName { database } => {
+                write!(
+                    f,
+                    "The database name '{}' is not valid. Use only alphanumeric characters, '-' and '_'",
+                    database
+                )
+            }
+            Self::Sqlite { path, error } => {
+                write!(
+                    f,
+                    "Error opening the SQL database {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            Self::Interrupted { database } => {
+                write!(
+                    f,
+                    "The statement in the SQL database {} was interrupted because it took too long",
+                    database
+                )
+            }
+            Self::Statement { database, error } => {
+                write!(
+                    f,
+                    "Error running the statement in the SQL database {}: {}",
+                    database, error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SQLError {}
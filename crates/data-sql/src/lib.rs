@@ -0,0 +1,124 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+mod database;
+pub mod errors;
+
+pub use database::{SQLCancellation, SQLDatabase, SQLRows, SQLValue};
+use errors::{Result, SQLError};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use wws_store::STORE_FOLDER;
+
+/// Folder inside the project store that keeps the SQL databases
+const SQL_FOLDER: &str = "sql";
+
+/// Extension of the database files
+const SQLITE_EXTENSION: &str = "sqlite";
+
+/// The SQL configuration. This information is read from workers TOML files.
+#[derive(Deserialize, Clone, Default)]
+pub struct SQLConfigData {
+    /// The databases the worker can access. Any other database is rejected
+    pub databases: Vec<String>,
+}
+
+/// The SQL databases of a project. Every database is a SQLite file in
+/// the `.wws/sql` folder. Workers share the databases with the same name
+#[derive(Default)]
+pub struct SQL {
+    /// The databases that were already opened
+    databases: RwLock<HashMap<String, Arc<SQLDatabase>>>,
+    /// The project root. Databases are saved inside its `.wws` folder
+    project_root: PathBuf,
+}
+
+impl SQL {
+    /// Initializes the SQL connector for the given project. Databases are
+    /// opened the first time a worker accesses them
+    pub fn new(project_root: PathBuf) -> Self {
+        Self {
+            databases: RwLock::default(),
+            project_root,
+        }
+    }
+
+    /// Returns the databases a worker can access, indexed by name.
+    /// Missing databases are created.
+    pub fn open_databases(
+        &self,
+        config: &SQLConfigData,
+    ) -> Result<HashMap<String, Arc<SQLDatabase>>> {
+        config
+            .databases
+            .iter()
+            .map(|name| Ok((name.clone(), self.open_database(name)?)))
+            .collect()
+    }
+
+    /// Look for an opened database or open it
+    fn open_database(&self, name: &str) -> Result<Arc<SQLDatabase>> {
+        if let Some(database) = self
+            .databases
+            .read()
+            .expect("error locking the SQL databases for reading")
+            .get(name)
+        {
+            return Ok(Arc::clone(database));
+        }
+
+        let mut databases = self
+            .databases
+            .write()
+            .expect("error locking the SQL databases for writing");
+
+        // Another request may open it while waiting for the lock
+        if let Some(database) = databases.get(name) {
+            return Ok(Arc::clone(database));
+        }
+
+        if !is_valid_name(name) {
+            return Err(SQLError::InvalidName {
+                database: name.to_string(),
+            });
+        }
+
+        let folder = self.project_root.join(STORE_FOLDER).join(SQL_FOLDER);
+        fs::create_dir_all(&folder).map_err(|error| SQLError::CannotCreateFolder {
+            path: folder.clone(),
+            error,
+        })?;
+
+        let path = folder.join(format!("{name}.{SQLITE_EXTENSION}"));
+        let database = Arc::new(SQLDatabase::open(name, &path)?);
+        databases.insert(name.to_string(), Arc::clone(&database));
+
+        Ok(database)
+    }
+}
+
+/// The database name is used as the file name, so it can't contain
+/// path separators or any other special character
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_database_names() {
+        assert!(is_valid_name("todos"));
+        assert!(is_valid_name("my-app_2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("../todos"));
+        assert!(!is_valid_name("todos.sqlite"));
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::files::{ERROR_FILE_NAME, NOT_FOUND_FILE_NAME};
+use crate::route::{folder_contains, register_worker, RouteSource};
+use std::path::{Path, PathBuf};
+use wws_config::Config as ProjectConfig;
+use wws_worker::errors::WorkerError;
+
+/// The errors the error workers reply to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// There's no route or asset for the request
+    NotFound,
+    /// The worker failed to process the request
+    Error,
+}
+
+impl ErrorKind {
+    /// Name of the worker files for this kind, without the extension
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::NotFound => NOT_FOUND_FILE_NAME,
+            Self::Error => ERROR_FILE_NAME,
+        }
+    }
+}
+
+/// A worker that replies when the server cannot serve a request in its
+/// folder and subfolders. It comes from a `_404` or `_error` file, like
+/// `api/_error.js`, or from the `errors` section of the routes manifest.
+#[derive(Clone, Debug)]
+pub struct ErrorWorker {
+    pub kind: ErrorKind,
+    /// The wasm module that will manage the errors
+    pub handler: PathBuf,
+    /// The URL path of the folder. The workers from the routes
+    /// manifest apply to the whole project
+    pub path: String,
+    /// The associated worker
+    pub worker: String,
+    /// Where the error worker comes from
+    pub source: RouteSource,
+}
+
+impl ErrorWorker {
+    /// Initialize a new error worker for the given folder. It loads the
+    /// worker and registers it.
+    pub fn try_new(
+        base_path: &Path,
+        filepath: PathBuf,
+        kind: ErrorKind,
+        folder_path: String,
+        source: RouteSource,
+        project_config: &ProjectConfig,
+    ) -> Result<Self, WorkerError> {
+        let worker_id = register_worker(base_path, &filepath, project_config)?;
+
+        Ok(Self {
+            kind,
+            handler: filepath,
+            path: folder_path,
+            worker: worker_id,
+            source,
+        })
+    }
+
+    /// Check if the error worker applies to the given path
+    pub fn applies_to(&self, path: &str) -> bool {
+        folder_contains(&self.path, path)
+    }
+
+    /// Number of folders between the project root and the error worker.
+    /// The inner ones have more priority
+    pub fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+}
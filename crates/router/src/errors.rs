@@ -0,0 +1,54 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+use wws_config::errors::ConfigError;
+use wws_worker::errors::WorkerError;
+
+pub type Result<T> = std::result::Result<T, RouterError>;
+
+#[derive(Debug)]
+pub enum RouterError {
+    /// The routes manifest can't be read or parsed
+    CannotLoadManifest(ConfigError),
+    /// A worker in the routes manifest can't be loaded
+    CannotLoadWorker { worker: PathBuf, error: WorkerError },
+    /// Two entries handle the same path and methods
+    Conflict {
+        path: String,
+        first: String,
+        second: String,
+    },
+    /// A path in the routes manifest is not valid
+    InvalidPath { path: String, reason: String },
+}
+
+impl std::fmt::Display for RouterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CannotLoadManifest(err) => write!(f, "Could not load the routes: {err}"),
+            Self::CannotLoadWorker { worker, error } => write!(
+                f,
+                "Could not load the worker {} from the routes: {error}",
+                worker.display()
+            ),
+            Self::Conflict {
+                path,
+                first,
+                second,
+            } => write!(
+                f,
+                "The path {path} is handled by both the {first} and the {second}"
+            ),
+            Self::InvalidPath { path, reason } => {
+                write!(f, "The path {path} in the routes is not valid: {reason}")
+            }
+        }
+    }
+}
+
+impl From<ConfigError> for RouterError {
+    fn from(error: ConfigError) -> Self {
+        RouterError::CannotLoadManifest(error)
+    }
+}
@@ -7,6 +7,12 @@ use wax::{Glob, WalkEntry};
 use wws_store::STORE_FOLDER;
 
 const IGNORE_PATH_PREFIX: &str = "_";
+/// Name of the middleware files, without the extension
+pub const MIDDLEWARE_FILE_NAME: &str = "_middleware";
+/// Name of the workers that reply to the requests without a route
+pub const NOT_FOUND_FILE_NAME: &str = "_404";
+/// Name of the workers that reply when a worker fails
+pub const ERROR_FILE_NAME: &str = "_error";
 
 /// Manages the files associated to a Wasm Workers Run.
 /// It uses glob patterns to detect the workers and
@@ -17,8 +23,14 @@ pub struct Files<'t> {
     root: PathBuf,
     /// Defines pattern for files considered as workers
     include_pattern: Glob<'t>,
+    /// File extensions of the workers
+    file_extensions: Vec<String>,
     /// Defines patterns to exclude when traversing for workers
     ignore_patterns: Vec<Glob<'t>>,
+    /// Defines patterns to exclude when traversing for special workers like
+    /// middlewares. They start with `_`, so only the folders with this
+    /// prefix are ignored
+    special_ignore_patterns: Vec<Glob<'t>>,
 }
 
 impl<'t> Files<'t> {
@@ -30,8 +42,10 @@ impl<'t> Files<'t> {
     pub fn new(root: &Path, file_extensions: Vec<String>, ignore_patterns: Vec<String>) -> Self {
         Self {
             root: root.to_path_buf(),
-            include_pattern: Self::build_include_pattern(file_extensions),
-            ignore_patterns: Self::build_ignore_patterns(ignore_patterns),
+            include_pattern: Self::build_include_pattern("*", file_extensions.clone()),
+            file_extensions,
+            ignore_patterns: Self::build_ignore_patterns(ignore_patterns.clone(), true),
+            special_ignore_patterns: Self::build_ignore_patterns(ignore_patterns, false),
         }
     }
 
@@ -47,22 +61,58 @@ impl<'t> Files<'t> {
             .collect();
     }
 
-    fn build_include_pattern(file_extensions: Vec<String>) -> Glob<'t> {
+    /// Walk through the middleware files of this project
+    pub fn walk_middlewares(&self) -> Vec<WalkEntry> {
+        self.walk_special(MIDDLEWARE_FILE_NAME)
+    }
+
+    /// Walk through the workers with the given name, like `_404`. These
+    /// special workers are not routes. The ones inside ignored folders
+    /// are skipped too
+    pub fn walk_special(&self, file_name: &str) -> Vec<WalkEntry> {
+        return Self::build_include_pattern(file_name, self.file_extensions.clone())
+            .walk(&self.root)
+            .not(self.special_ignore_patterns.clone())
+            .expect(
+                "Failed to walk the tree when processing special workers in the current directory",
+            )
+            .map(|e| e.unwrap())
+            .filter(|e| {
+                let path = e.path().strip_prefix(&self.root).unwrap_or(e.path());
+                !Self::in_ignored_folder(path)
+            })
+            .collect();
+    }
+
+    /// Check if any of the parent folders starts with the ignore prefix
+    fn in_ignored_folder(path: &Path) -> bool {
+        path.parent().map_or(false, |parent| {
+            parent.components().any(|c| {
+                c.as_os_str()
+                    .to_string_lossy()
+                    .starts_with(IGNORE_PATH_PREFIX)
+            })
+        })
+    }
+
+    fn build_include_pattern(file_name: &str, file_extensions: Vec<String>) -> Glob<'t> {
         let mut file_extensions = file_extensions;
         for default_extension in Self::DEFAULT_EXTENSIONS {
             file_extensions.push(default_extension.to_string());
         }
 
-        let include_pattern = format!("**/*.{{{}}}", file_extensions.join(","));
+        let include_pattern = format!("**/{file_name}.{{{}}}", file_extensions.join(","));
         Glob::from_str(include_pattern.as_str()).expect("Failed to parse include pattern!")
     }
 
-    fn build_ignore_patterns(ignore_patterns: Vec<String>) -> Vec<Glob<'t>> {
-        let default_ignore_patterns = vec![
+    fn build_ignore_patterns(ignore_patterns: Vec<String>, ignore_prefix: bool) -> Vec<Glob<'t>> {
+        let mut default_ignore_patterns = vec![
             format!("**/{}/**", Self::PUBLIC_ASSETS_FOLDER),
             format!("**/{}/**", STORE_FOLDER),
-            format!("**/{}*/**", IGNORE_PATH_PREFIX),
         ];
+        if ignore_prefix {
+            default_ignore_patterns.push(format!("**/{}*/**", IGNORE_PATH_PREFIX));
+        }
 
         let mut result = default_ignore_patterns;
         result.extend(ignore_patterns);
@@ -155,6 +205,36 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn walk_middlewares() {
+        let files = Files::new(Path::new("tests/data/files"), vec![], vec![]);
+
+        let mut expected = HashSet::new();
+        expected.insert(PathBuf::from_slash("tests/data/files/_middleware.js"));
+        expected.insert(PathBuf::from_slash(
+            "tests/data/files/examples/_middleware.wasm",
+        ));
+
+        let mut actual = HashSet::new();
+        for entry in files.walk_middlewares() {
+            actual.insert(PathBuf::from_slash(String::from(
+                entry.path().to_string_lossy(),
+            )));
+        }
+
+        assert_eq!(expected, actual);
+
+        let errors: Vec<PathBuf> = files
+            .walk_special(ERROR_FILE_NAME)
+            .into_iter()
+            .map(|entry| PathBuf::from_slash(String::from(entry.path().to_string_lossy())))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![PathBuf::from_slash("tests/data/files/_error.js")]
+        );
+    }
+
     #[test]
     fn walk_ignore() {
         let files = Files::new(
@@ -6,35 +6,74 @@
 // based on the files in the given folder
 //
 
+mod error_worker;
+pub mod errors;
 mod files;
+mod manifest;
+mod middleware;
 mod route;
+mod watcher;
+use errors::{Result, RouterError};
 use files::Files;
+use manifest::Manifest;
+use route::{folder_path, path_shape};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use wws_config::Config;
 
-pub use route::{Route, WORKERS};
+pub use error_worker::{ErrorKind, ErrorWorker};
+pub use manifest::{Redirect, Rewrite};
+pub use middleware::Middleware;
+pub use route::{PathPattern, Route, RouteSource, WORKERS};
+pub use watcher::watch;
 
 /// Contains all registered routes
 #[derive(Clone, Default)]
 pub struct Routes {
     pub routes: Vec<Route>,
     pub prefix: String,
+    /// Redirects from the routes manifest
+    pub redirects: Vec<Redirect>,
+    /// Rewrites from the routes manifest
+    pub rewrites: Vec<Rewrite>,
+    /// Middlewares of the project. The outer ones go first
+    pub middlewares: Vec<Middleware>,
+    /// Workers that reply to the errors. The inner ones go last
+    pub error_workers: Vec<ErrorWorker>,
+}
+
+/// How the server replies to a request
+#[derive(Clone, Debug)]
+pub enum RouteMatch {
+    /// Redirect the client to a different location
+    Redirect { location: String, status: u16 },
+    /// Run the worker of the route. The path is different from the
+    /// requested one when a rewrite applies
+    Worker { route: Route, path: String },
+    /// There are routes for the path, but none of them accepts the
+    /// method. It includes the methods they accept
+    MethodNotAllowed { allowed: Vec<String> },
 }
 
 impl Routes {
     /// Initialize the list of routes from the given folder. This method will look for
     /// different files and will create the associated routes. This routing approach
     /// is pretty popular in web development and static sites.
+    ///
+    /// The routes, redirects and rewrites in the routes manifest are added to them.
+    /// It fails when a worker cannot be loaded, when the manifest is not valid or
+    /// when two routes conflict.
     pub fn new(
         path: &Path,
         base_prefix: &str,
         ignore_patterns: Vec<String>,
         config: &Config,
-    ) -> Self {
+    ) -> Result<Self> {
         let mut routes = Vec::new();
         let prefix = Self::format_prefix(base_prefix);
         let runtime_extensions = config.get_runtime_extensions(path);
+        let manifest = Manifest::load(path, &prefix, config)?;
 
         let files = Files::new(path, runtime_extensions, ignore_patterns);
 
@@ -42,23 +81,383 @@ impl Routes {
         for entry in files.walk() {
             route_paths.push(entry.into_path());
         }
+        let middleware_paths: Vec<PathBuf> = files
+            .walk_middlewares()
+            .into_iter()
+            .map(|entry| entry.into_path())
+            .collect();
 
-        println!("⏳ Loading workers from {} routes...", route_paths.len());
+        println!(
+            "⏳ Loading workers from {} routes...",
+            route_paths.len() + manifest.workers.len() + middleware_paths.len()
+        );
         let start = Instant::now();
         for route_path in route_paths {
-            routes.push(Route::new(path, route_path, &prefix, config));
+            let route =
+                Route::try_new(path, route_path.clone(), &prefix, config).map_err(|error| {
+                    RouterError::CannotLoadWorker {
+                        worker: route_path,
+                        error,
+                    }
+                })?;
+            routes.push(route);
+        }
+        for entry in manifest.workers {
+            let route = Route::try_from_manifest(
+                path,
+                entry.handler.clone(),
+                entry.path,
+                entry.methods,
+                config,
+            )
+            .map_err(|error| RouterError::CannotLoadWorker {
+                worker: entry.handler,
+                error,
+            })?;
+            routes.push(route);
         }
         routes.sort();
+
+        let mut middlewares = Vec::new();
+        for middleware_path in middleware_paths {
+            let middleware = Middleware::try_new(path, middleware_path.clone(), &prefix, config)
+                .map_err(|error| RouterError::CannotLoadWorker {
+                    worker: middleware_path,
+                    error,
+                })?;
+            middlewares.push(middleware);
+        }
+        middlewares.sort_by_key(Middleware::depth);
+        let error_workers =
+            Self::load_error_workers(path, &prefix, &files, &manifest.errors, config)?;
         println!("✅ Workers loaded in {:?}.", start.elapsed());
 
-        Self { routes, prefix }
+        let routes = Self {
+            routes,
+            prefix,
+            redirects: manifest.redirects,
+            rewrites: manifest.rewrites,
+            middlewares,
+            error_workers,
+        };
+        routes.check_conflicts()?;
+
+        Ok(routes)
+    }
+
+    /// Update the routes after a change in the project files. It walks the
+    /// project again with the same rules as `new` to detect added and removed
+    /// workers, and it reloads the workers affected by the changed files.
+    /// Workers that fail to load are skipped, so the server keeps running.
+    ///
+    /// The routes manifest is loaded again too. If it's not valid, the
+    /// previous routes, redirects and rewrites from the manifest are kept.
+    /// Middlewares follow the same rules as the workers. The error workers
+    /// are loaded again.
+    ///
+    /// The removed workers stay in the `WORKERS` registry, as requests
+    /// resolved with the previous routes may still use them. Compare the
+    /// `workers` before and after the update to remove them.
+    pub fn update(
+        &mut self,
+        path: &Path,
+        ignore_patterns: Vec<String>,
+        config: &Config,
+        changed_files: &[PathBuf],
+    ) {
+        let runtime_extensions = config.get_runtime_extensions(path);
+        let files = Files::new(path, runtime_extensions, ignore_patterns);
+        let route_paths: HashSet<PathBuf> = files
+            .walk()
+            .into_iter()
+            .map(|entry| entry.into_path())
+            .collect();
+        let middleware_paths: HashSet<PathBuf> = files
+            .walk_middlewares()
+            .into_iter()
+            .map(|entry| entry.into_path())
+            .collect();
+        let manifest = match Manifest::load(path, &self.prefix, config) {
+            Ok(manifest) => Some(manifest),
+            Err(err) => {
+                eprintln!("❌ Error loading the routes manifest: {err}");
+                None
+            }
+        };
+
+        // Remove the workers that are not present anymore. The manifest
+        // routes are created again from the new manifest
+        self.routes.retain(|route| match route.source {
+            RouteSource::File if route_paths.contains(&route.handler) => true,
+            RouteSource::File => {
+                println!("🗑️  Removed worker {}", route.handler.display());
+                false
+            }
+            RouteSource::Manifest => manifest.is_none(),
+        });
+
+        for route_path in route_paths {
+            let existing = self
+                .routes
+                .iter()
+                .position(|route| route.source == RouteSource::File && route.handler == route_path);
+
+            if existing.is_some() && !Self::is_affected(&route_path, changed_files) {
+                continue;
+            }
+
+            match Route::try_new(path, route_path.clone(), &self.prefix, config) {
+                Ok(route) => {
+                    println!(
+                        "🔄 Loaded worker {} => {}",
+                        route.path,
+                        route_path.display()
+                    );
+
+                    match existing {
+                        Some(index) => self.routes[index] = route,
+                        None => self.routes.push(route),
+                    }
+                }
+                Err(err) => {
+                    eprintln!(
+                        "❌ Error loading the worker {}: {err}",
+                        route_path.display()
+                    );
+                }
+            }
+        }
+
+        self.middlewares.retain(|middleware| {
+            let exists = middleware_paths.contains(&middleware.handler);
+            if !exists {
+                println!("🗑️  Removed middleware {}", middleware.handler.display());
+            }
+            exists
+        });
+
+        for middleware_path in middleware_paths {
+            let existing = self
+                .middlewares
+                .iter()
+                .position(|middleware| middleware.handler == middleware_path);
+
+            if existing.is_some() && !Self::is_affected(&middleware_path, changed_files) {
+                continue;
+            }
+
+            match Middleware::try_new(path, middleware_path.clone(), &self.prefix, config) {
+                Ok(middleware) => {
+                    println!(
+                        "🔄 Loaded middleware {} => {}",
+                        middleware.path,
+                        middleware_path.display()
+                    );
+
+                    match existing {
+                        Some(index) => self.middlewares[index] = middleware,
+                        None => self.middlewares.push(middleware),
+                    }
+                }
+                Err(err) => {
+                    eprintln!(
+                        "❌ Error loading the middleware {}: {err}",
+                        middleware_path.display()
+                    );
+                }
+            }
+        }
+        self.middlewares.sort_by_key(Middleware::depth);
+
+        // Keep the previous error workers from the manifest if it's not valid
+        let manifest_errors = match &manifest {
+            Some(manifest) => manifest.errors.clone(),
+            None => self
+                .error_workers
+                .iter()
+                .filter(|error_worker| error_worker.source == RouteSource::Manifest)
+                .map(|error_worker| (error_worker.kind, error_worker.handler.clone()))
+                .collect(),
+        };
+        match Self::load_error_workers(path, &self.prefix, &files, &manifest_errors, config) {
+            Ok(error_workers) => self.error_workers = error_workers,
+            Err(err) => eprintln!("❌ {err}"),
+        }
+
+        if let Some(manifest) = manifest {
+            for entry in manifest.workers {
+                match Route::try_from_manifest(
+                    path,
+                    entry.handler.clone(),
+                    entry.path,
+                    entry.methods,
+                    config,
+                ) {
+                    Ok(route) => self.routes.push(route),
+                    Err(err) => {
+                        eprintln!(
+                            "❌ Error loading the worker {}: {err}",
+                            entry.handler.display()
+                        );
+                    }
+                }
+            }
+
+            self.redirects = manifest.redirects;
+            self.rewrites = manifest.rewrites;
+        }
+
+        self.routes.sort();
+
+        if let Err(err) = self.check_conflicts() {
+            eprintln!("❌ {err}");
+        }
+    }
+
+    /// Returns the identifiers of the workers that the routes, middlewares
+    /// and error workers use. A worker may serve several routes.
+    pub fn workers(&self) -> HashSet<String> {
+        self.routes
+            .iter()
+            .map(|route| route.worker.clone())
+            .chain(self.middlewares.iter().map(|m| m.worker.clone()))
+            .chain(self.error_workers.iter().map(|e| e.worker.clone()))
+            .collect()
+    }
+
+    /// Load the error workers from the project files and the routes manifest.
+    /// The ones from the manifest apply to the whole project, but the files
+    /// have more priority.
+    fn load_error_workers(
+        path: &Path,
+        prefix: &str,
+        files: &Files,
+        manifest_errors: &[(ErrorKind, PathBuf)],
+        config: &Config,
+    ) -> Result<Vec<ErrorWorker>> {
+        let mut entries: Vec<(ErrorKind, PathBuf, String, RouteSource)> = manifest_errors
+            .iter()
+            .map(|(kind, handler)| {
+                (
+                    *kind,
+                    handler.clone(),
+                    prefix.to_string(),
+                    RouteSource::Manifest,
+                )
+            })
+            .collect();
+        for kind in [ErrorKind::NotFound, ErrorKind::Error] {
+            for entry in files.walk_special(kind.file_name()) {
+                let handler = entry.into_path();
+                let folder = folder_path(path, &handler, prefix);
+
+                entries.push((kind, handler, folder, RouteSource::File));
+            }
+        }
+
+        let mut error_workers = Vec::new();
+        for (kind, handler, folder, source) in entries {
+            let error_worker =
+                ErrorWorker::try_new(path, handler.clone(), kind, folder, source, config).map_err(
+                    |error| RouterError::CannotLoadWorker {
+                        worker: handler,
+                        error,
+                    },
+                )?;
+            error_workers.push(error_worker);
+        }
+        // It keeps the manifest entries first for the same depth
+        error_workers.sort_by_key(ErrorWorker::depth);
+
+        Ok(error_workers)
+    }
+
+    /// Check that every path and method is handled by a single entry.
+    /// Files without a method suffix don't conflict with each other, so
+    /// only the entries from the routes manifest and the files limited
+    /// to some methods are checked.
+    fn check_conflicts(&self) -> Result<()> {
+        // The methods of every entry. Redirects and rewrites apply to all of them
+        let mut entries: HashMap<String, Vec<(String, Option<&Vec<String>>, bool)>> =
+            HashMap::new();
+
+        for route in self.routes.iter() {
+            entries.entry(path_shape(&route.path)).or_default().push((
+                format!("worker {}", route.handler.display()),
+                route.methods.as_ref(),
+                route.source == RouteSource::Manifest,
+            ));
+        }
+        for redirect in self.redirects.iter() {
+            entries
+                .entry(path_shape(&redirect.from.path))
+                .or_default()
+                .push((format!("redirect to {}", redirect.to), None, true));
+        }
+        for rewrite in self.rewrites.iter() {
+            entries
+                .entry(path_shape(&rewrite.from.path))
+                .or_default()
+                .push((format!("rewrite to {}", rewrite.to), None, true));
+        }
+
+        for (path, entries) in entries.iter() {
+            for (index, (first, first_methods, first_manifest)) in entries.iter().enumerate() {
+                for (second, second_methods, second_manifest) in entries.iter().skip(index + 1) {
+                    let overlap = match (first_methods, second_methods) {
+                        (Some(first), Some(second)) => first.iter().any(|m| second.contains(m)),
+                        _ => true,
+                    };
+
+                    let explicit = *first_manifest
+                        || *second_manifest
+                        || (first_methods.is_some() && second_methods.is_some());
+
+                    if overlap && explicit {
+                        return Err(RouterError::Conflict {
+                            path: path.clone(),
+                            first: first.clone(),
+                            second: second.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if any of the changed files affects the given worker. It
+    /// considers the worker file and its TOML configuration.
+    fn is_affected(route_path: &Path, changed_files: &[PathBuf]) -> bool {
+        let Some(worker) = Self::canonicalize(route_path) else {
+            return false;
+        };
+        let config = worker.with_extension("toml");
+
+        changed_files
+            .iter()
+            .filter_map(|file| Self::canonicalize(file))
+            .any(|file| file == worker || file == config)
+    }
+
+    /// Returns the absolute path for the given file. It canonicalizes the
+    /// parent folder, so it works for removed files too.
+    fn canonicalize(path: &Path) -> Option<PathBuf> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let parent = parent.canonicalize().ok()?;
+
+        Some(parent.join(path.file_name()?))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Route> {
         self.routes.iter()
     }
 
-    /// Provides the **first** route that can handle the given path.
+    /// Provides the **first** route that can handle the given method and path.
     /// This only works because the routes are already sorted.
     /// Because a '/a/b' route may be served by:
     /// - /a/b.js
@@ -66,8 +465,101 @@ impl Routes {
     /// - /[id]/b.wasm
     /// - /[id]/[other].wasm
     /// - /[id]/[..all].wasm
-    pub fn retrieve_best_route<'a>(&'a self, path: &str) -> Option<&'a Route> {
-        self.iter().find(|r| r.can_manage(path))
+    pub fn retrieve_best_route<'a>(&'a self, method: &str, path: &str) -> Option<&'a Route> {
+        let mut routes = self
+            .iter()
+            .filter(|r| r.can_manage(path) && r.can_manage_method(method));
+        let best = routes.next()?;
+
+        // A `[id].head.js` worker goes before `[id].get.js` for the HEAD requests
+        if best.methods.is_some() && !best.is_limited_to(method) {
+            if let Some(route) = routes
+                .take_while(|r| r.path == best.path)
+                .find(|r| r.is_limited_to(method))
+            {
+                return Some(route);
+            }
+        }
+
+        Some(best)
+    }
+
+    /// Find how to reply to the given request. Redirects apply first. Then,
+    /// the path may be rewritten before looking for the best route.
+    pub fn resolve(&self, method: &str, path: &str) -> Option<RouteMatch> {
+        if let Some((location, status)) = self
+            .redirects
+            .iter()
+            .find_map(|redirect| Some((redirect.location(path)?, redirect.status)))
+        {
+            return Some(RouteMatch::Redirect { location, status });
+        }
+
+        let path = self
+            .rewrites
+            .iter()
+            .find_map(|rewrite| rewrite.rewrite(path))
+            .unwrap_or_else(|| path.to_string());
+
+        match self.retrieve_best_route(method, &path) {
+            Some(route) => Some(RouteMatch::Worker {
+                route: route.clone(),
+                path,
+            }),
+            None => {
+                let allowed = self.allowed_methods(&path);
+
+                (!allowed.is_empty()).then_some(RouteMatch::MethodNotAllowed { allowed })
+            }
+        }
+    }
+
+    /// Returns the methods accepted by the routes that can handle the given
+    /// path. It's only relevant when all of them are limited to some methods.
+    /// `HEAD` is allowed with `GET`, and the server answers `OPTIONS` for
+    /// any path with routes
+    pub fn allowed_methods(&self, path: &str) -> Vec<String> {
+        let mut allowed: Vec<String> = self
+            .iter()
+            .filter(|route| route.can_manage(path))
+            .filter_map(|route| route.methods.clone())
+            .flatten()
+            .collect();
+
+        if allowed.is_empty() {
+            return allowed;
+        }
+        if allowed
+            .iter()
+            .any(|method| method.eq_ignore_ascii_case("GET"))
+        {
+            allowed.push("HEAD".to_string());
+        }
+        allowed.push("OPTIONS".to_string());
+        allowed.sort();
+        allowed.dedup();
+
+        allowed
+    }
+
+    /// Returns the middlewares that apply to the given path. The outer
+    /// ones go first
+    pub fn middlewares_for(&self, path: &str) -> Vec<Middleware> {
+        self.middlewares
+            .iter()
+            .filter(|middleware| middleware.applies_to(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the error worker of the given kind for the path. The ones in
+    /// the inner folders have more priority
+    pub fn error_worker_for(&self, kind: ErrorKind, path: &str) -> Option<ErrorWorker> {
+        self.error_workers
+            .iter()
+            .rev()
+            .find(|error_worker| error_worker.kind == kind && error_worker.applies_to(path))
+            .cloned()
     }
 
     /// Defines a prefix in the context of the application.
@@ -102,6 +594,42 @@ impl Routes {
     }
 }
 
+/// Returns the patterns to ignore the given folders when they are inside
+/// the project. This way, a project doesn't load the workers of other
+/// projects nested in it, like the ones of the virtual hosts.
+pub fn nested_ignore_patterns(project_path: &Path, folders: &[PathBuf]) -> Vec<String> {
+    let project_path = project_path
+        .canonicalize()
+        .unwrap_or_else(|_| project_path.to_path_buf());
+
+    folders
+        .iter()
+        .filter_map(|folder| {
+            let folder = folder.canonicalize().unwrap_or_else(|_| folder.clone());
+            let relative = folder.strip_prefix(&project_path).ok()?;
+            let segments: Vec<String> = relative
+                .components()
+                .map(|component| escape_glob(&component.as_os_str().to_string_lossy()))
+                .collect();
+
+            (!segments.is_empty()).then(|| format!("{}/**", segments.join("/")))
+        })
+        .collect()
+}
+
+/// Escape the characters with a meaning in the glob patterns
+fn escape_glob(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '?' | '*' | '$' | ':' | '<' | '>' | '(' | ')' | '[' | ']' | '{' | '}' | ',' | '\\' => {
+                vec!['\\', c]
+            }
+            _ => vec![c],
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,14 +642,16 @@ mod tests {
             "",
             Vec::new(),
             &project_config,
-        );
+        )
+        .unwrap();
 
         let mut sorted_router = Routes::new(
             Path::new("../../tests/data/params"),
             "",
             Vec::new(),
             &project_config,
-        );
+        )
+        .unwrap();
 
         sorted_router.routes.sort();
 
@@ -136,7 +666,8 @@ mod tests {
             "",
             Vec::new(),
             &project_config,
-        );
+        )
+        .unwrap();
 
         let tests = [
             ("/any", Some("/[id]")),
@@ -150,7 +681,7 @@ mod tests {
         ];
 
         for (given_path, expected_path) in tests {
-            let route = router.retrieve_best_route(given_path);
+            let route = router.retrieve_best_route("GET", given_path);
 
             if let Some(path) = expected_path {
                 assert!(route.is_some());
@@ -161,6 +692,193 @@ mod tests {
         }
     }
 
+    #[test]
+    fn resolve_manifest_routes() {
+        let project_config = Config::default();
+        let router = Routes::new(
+            Path::new("../../tests/data/manifest"),
+            "",
+            Vec::new(),
+            &project_config,
+        )
+        .unwrap();
+
+        match router.resolve("GET", "/users/1") {
+            Some(RouteMatch::Worker { route, path }) => {
+                assert_eq!(route.path, "/users/[id]");
+                assert_eq!(path, "/users/1");
+            }
+            _ => panic!("the manifest route was not found"),
+        }
+        match router.resolve("POST", "/users/1") {
+            Some(RouteMatch::MethodNotAllowed { allowed }) => {
+                assert_eq!(allowed, vec!["GET", "HEAD", "OPTIONS"])
+            }
+            _ => panic!("the method filter was not applied"),
+        }
+
+        match router.resolve("GET", "/old/1") {
+            Some(RouteMatch::Redirect { location, status }) => {
+                assert_eq!(location, "/users/1");
+                assert_eq!(status, 307);
+            }
+            _ => panic!("the redirect was not found"),
+        }
+
+        match router.resolve("GET", "/profile/2") {
+            Some(RouteMatch::Worker { route, path }) => {
+                assert_eq!(route.path, "/users/[id]");
+                assert_eq!(path, "/users/2");
+            }
+            _ => panic!("the rewrite was not applied"),
+        }
+
+        // File routes are still available
+        assert!(router.retrieve_best_route("POST", "/fixed").is_some());
+    }
+
+    #[test]
+    fn resolve_method_routes() {
+        let project_config = Config::default();
+        let router = Routes::new(
+            Path::new("../../tests/data/methods"),
+            "",
+            Vec::new(),
+            &project_config,
+        )
+        .unwrap();
+
+        let tests = [
+            ("GET", "/users/1", Some("[id].get.wasm")),
+            ("DELETE", "/users/1", Some("[id].delete.wasm")),
+            ("GET", "/users", Some("index.get.wasm")),
+            ("POST", "/users", Some("index.wasm")),
+        ];
+
+        for (method, path, expected) in tests {
+            match router.resolve(method, path) {
+                Some(RouteMatch::Worker { route, .. }) => assert_eq!(
+                    route.handler.file_name().and_then(|name| name.to_str()),
+                    expected
+                ),
+                _ => panic!("no route for {method} {path}"),
+            }
+        }
+
+        match router.resolve("PUT", "/users/1") {
+            Some(RouteMatch::MethodNotAllowed { allowed }) => {
+                assert_eq!(allowed, vec!["DELETE", "GET", "HEAD", "OPTIONS"])
+            }
+            _ => panic!("the method should not be allowed"),
+        }
+        match router.resolve("HEAD", "/users/1") {
+            Some(RouteMatch::Worker { route, .. }) => assert!(route.is_limited_to("GET")),
+            _ => panic!("HEAD requests should use the GET route"),
+        }
+        assert!(matches!(
+            router.resolve("OPTIONS", "/users/1"),
+            Some(RouteMatch::MethodNotAllowed { .. })
+        ));
+        assert!(router.resolve("GET", "/posts").is_none());
+    }
+
+    #[test]
+    fn ignore_nested_projects() {
+        let project_config = Config::default();
+        let project_path = Path::new("../../tests/data/hosts");
+        let host_paths = vec![
+            project_path.join("sites/shop"),
+            PathBuf::from("../../tests/data/methods"),
+        ];
+
+        let ignore_patterns = nested_ignore_patterns(project_path, &host_paths);
+        assert_eq!(ignore_patterns, vec!["sites/shop/**"]);
+
+        let router = Routes::new(project_path, "", ignore_patterns, &project_config).unwrap();
+        let paths: Vec<&str> = router.iter().map(|route| route.path.as_str()).collect();
+        assert_eq!(paths, vec!["/"]);
+
+        // The nested project still loads its own workers
+        let router = Routes::new(&host_paths[0], "", Vec::new(), &project_config).unwrap();
+        assert!(router.retrieve_best_route("GET", "/").is_some());
+
+        assert_eq!(escape_glob("[id]"), "\\[id\\]");
+    }
+
+    #[test]
+    fn retrieve_middlewares() {
+        let project_config = Config::default();
+        let router = Routes::new(
+            Path::new("../../tests/data/middlewares"),
+            "",
+            Vec::new(),
+            &project_config,
+        )
+        .unwrap();
+
+        let paths = |path: &str| -> Vec<String> {
+            router
+                .middlewares_for(path)
+                .into_iter()
+                .map(|middleware| middleware.path)
+                .collect()
+        };
+
+        assert_eq!(paths("/"), vec![""]);
+        assert_eq!(paths("/api/users"), vec!["", "/api"]);
+        assert_eq!(paths("/apis"), vec![""]);
+        // Middlewares are not routes
+        assert!(router.resolve("GET", "/_middleware").is_none());
+        assert!(router.resolve("GET", "/api/_middleware").is_none());
+    }
+
+    #[test]
+    fn retrieve_error_workers() {
+        let project_config = Config::default();
+        let router = Routes::new(
+            Path::new("../../tests/data/errors"),
+            "",
+            Vec::new(),
+            &project_config,
+        )
+        .unwrap();
+
+        let handler = |kind: ErrorKind, path: &str| -> Option<PathBuf> {
+            router
+                .error_worker_for(kind, path)
+                .map(|error_worker| error_worker.handler)
+        };
+        let root = Path::new("../../tests/data/errors");
+
+        assert_eq!(
+            handler(ErrorKind::NotFound, "/missing"),
+            Some(root.join("_handlers/not-found.wasm"))
+        );
+        assert_eq!(
+            handler(ErrorKind::NotFound, "/api/missing"),
+            Some(root.join("api/_404.wasm"))
+        );
+        assert_eq!(
+            handler(ErrorKind::Error, "/api/users"),
+            Some(root.join("_error.wasm"))
+        );
+        // Error workers are not routes
+        assert!(router.resolve("GET", "/_error").is_none());
+    }
+
+    #[test]
+    fn report_manifest_conflicts() {
+        let project_config = Config::default();
+        let router = Routes::new(
+            Path::new("../../tests/data/manifest-conflict"),
+            "",
+            Vec::new(),
+            &project_config,
+        );
+
+        assert!(matches!(router, Err(RouterError::Conflict { .. })));
+    }
+
     #[test]
     fn format_provided_prefix() {
         let tests = [
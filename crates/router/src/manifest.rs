@@ -0,0 +1,209 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error_worker::ErrorKind;
+use crate::errors::{Result, RouterError};
+use crate::route::{fill_params, param_names, PathPattern};
+use std::path::{Path, PathBuf};
+use wws_config::Config;
+
+/// Status codes of the redirects. Unlike 301 and 302, clients keep
+/// the method and body of the request.
+const TEMPORARY_REDIRECT: u16 = 307;
+const PERMANENT_REDIRECT: u16 = 308;
+
+/// A worker declared in the routes manifest
+pub(crate) struct ManifestWorker {
+    /// URL path of the route, including the prefix
+    pub path: String,
+    /// The worker file
+    pub handler: PathBuf,
+    /// The HTTP methods the route accepts
+    pub methods: Option<Vec<String>>,
+}
+
+/// Redirect the clients from a path to a different location
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    pub from: PathPattern,
+    /// The new location. It may include the parameters of `from`
+    pub to: String,
+    pub status: u16,
+}
+
+impl Redirect {
+    /// Returns the new location for the given path if it matches
+    pub fn location(&self, path: &str) -> Option<String> {
+        self.from
+            .matches(path)
+            .then(|| fill_params(&self.to, &self.from.params(path)))
+    }
+}
+
+/// Serve a path with the route of a different one
+#[derive(Clone, Debug)]
+pub struct Rewrite {
+    pub from: PathPattern,
+    /// The path of the route that serves the request. It may include
+    /// the parameters of `from`
+    pub to: String,
+}
+
+impl Rewrite {
+    /// Returns the new path for the given one if it matches
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        self.from
+            .matches(path)
+            .then(|| fill_params(&self.to, &self.from.params(path)))
+    }
+}
+
+/// The explicit routes of a project. They come from the `routes.toml`
+/// file and the `routes` section of the `.wws.toml` file.
+#[derive(Default)]
+pub(crate) struct Manifest {
+    pub workers: Vec<ManifestWorker>,
+    pub redirects: Vec<Redirect>,
+    pub rewrites: Vec<Rewrite>,
+    /// The error workers for the whole project
+    pub errors: Vec<(ErrorKind, PathBuf)>,
+}
+
+impl Manifest {
+    /// Load and validate the routes manifest of the project. The given
+    /// prefix applies to all the paths.
+    pub fn load(project_root: &Path, prefix: &str, config: &Config) -> Result<Self> {
+        let routes = config.load_routes(project_root)?;
+        let mut manifest = Self::default();
+
+        for entry in routes.workers {
+            check_path(&entry.path)?;
+
+            let methods: Vec<String> = entry
+                .methods
+                .iter()
+                .map(|method| method.to_uppercase())
+                .collect();
+
+            manifest.workers.push(ManifestWorker {
+                path: format!("{prefix}{}", entry.path),
+                handler: project_root.join(&entry.worker),
+                methods: (!methods.is_empty()).then_some(methods),
+            });
+        }
+
+        for entry in routes.redirects {
+            check_path(&entry.from)?;
+            check_params(&entry.from, &entry.to)?;
+
+            // Local paths include the prefix too
+            let to = if entry.to.starts_with('/') {
+                format!("{prefix}{}", entry.to)
+            } else {
+                entry.to
+            };
+
+            manifest.redirects.push(Redirect {
+                from: PathPattern::new(&format!("{prefix}{}", entry.from)),
+                to,
+                status: if entry.permanent {
+                    PERMANENT_REDIRECT
+                } else {
+                    TEMPORARY_REDIRECT
+                },
+            });
+        }
+
+        for entry in routes.rewrites {
+            check_path(&entry.from)?;
+            check_path(&entry.to)?;
+            check_params(&entry.from, &entry.to)?;
+
+            manifest.rewrites.push(Rewrite {
+                from: PathPattern::new(&format!("{prefix}{}", entry.from)),
+                to: format!("{prefix}{}", entry.to),
+            });
+        }
+
+        for (kind, worker) in [
+            (ErrorKind::NotFound, routes.errors.not_found),
+            (ErrorKind::Error, routes.errors.error),
+        ] {
+            if let Some(worker) = worker {
+                manifest.errors.push((kind, project_root.join(worker)));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// The paths in the manifest must be absolute URL paths
+fn check_path(path: &str) -> Result<()> {
+    if path.starts_with('/') {
+        Ok(())
+    } else {
+        Err(RouterError::InvalidPath {
+            path: path.to_string(),
+            reason: "it must start with /".to_string(),
+        })
+    }
+}
+
+/// The target of a redirect or rewrite can only use the parameters
+/// of its source
+fn check_params(from: &str, to: &str) -> Result<()> {
+    let from_params = param_names(from);
+
+    match param_names(to)
+        .into_iter()
+        .find(|param| !from_params.contains(param))
+    {
+        Some(param) => Err(RouterError::InvalidPath {
+            path: to.to_string(),
+            reason: format!("the parameter {param} is not present in {from}"),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_with_params() {
+        let redirect = Redirect {
+            from: PathPattern::new("/docs/[...page]"),
+            to: "https://docs.example.com/[...page]".to_string(),
+            status: PERMANENT_REDIRECT,
+        };
+
+        assert_eq!(
+            redirect.location("/docs/api/workers"),
+            Some("https://docs.example.com/api/workers".to_string())
+        );
+        assert_eq!(redirect.location("/blog/post"), None);
+    }
+
+    #[test]
+    fn rewrite_with_params() {
+        let rewrite = Rewrite {
+            from: PathPattern::new("/blog/[slug]"),
+            to: "/posts/[slug]".to_string(),
+        };
+
+        assert_eq!(
+            rewrite.rewrite("/blog/hello"),
+            Some("/posts/hello".to_string())
+        );
+        assert_eq!(rewrite.rewrite("/blog/hello/world"), None);
+    }
+
+    #[test]
+    fn reject_unknown_params() {
+        assert!(check_params("/blog/[slug]", "/posts/[slug]").is_ok());
+        assert!(check_params("/blog/[slug]", "/posts/[id]").is_err());
+        assert!(check_path("blog").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::route::{folder_contains, folder_path, register_worker};
+use std::path::{Path, PathBuf};
+use wws_config::Config as ProjectConfig;
+use wws_worker::errors::WorkerError;
+
+/// A worker that runs before the routes in its folder and subfolders.
+/// It comes from a `_middleware` file, like `api/_middleware.js`.
+///
+/// # Examples
+///
+/// ```
+/// _middleware.js          =>  /
+/// api/_middleware.wasm    =>  /api, /api/users, /api/users/[id]...
+/// ```
+#[derive(Clone, Debug)]
+pub struct Middleware {
+    /// The wasm module that will manage the requests
+    pub handler: PathBuf,
+    /// The URL path of the folder
+    pub path: String,
+    /// The associated worker
+    pub worker: String,
+}
+
+impl Middleware {
+    /// Initialize a new middleware from the given folder and filepath. It
+    /// loads the worker and registers it.
+    pub fn try_new(
+        base_path: &Path,
+        filepath: PathBuf,
+        prefix: &str,
+        project_config: &ProjectConfig,
+    ) -> Result<Self, WorkerError> {
+        let worker_id = register_worker(base_path, &filepath, project_config)?;
+
+        Ok(Self {
+            path: folder_path(base_path, &filepath, prefix),
+            handler: filepath,
+            worker: worker_id,
+        })
+    }
+
+    /// Check if the middleware applies to the given path
+    pub fn applies_to(&self, path: &str) -> bool {
+        folder_contains(&self.path, path)
+    }
+
+    /// Number of folders between the project root and the middleware.
+    /// Outer middlewares run first
+    pub fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn middleware_applies_to_subtree() {
+        let middleware = Middleware {
+            handler: PathBuf::from("api/_middleware.js"),
+            path: "/api".to_string(),
+            worker: String::new(),
+        };
+
+        assert!(middleware.applies_to("/api"));
+        assert!(middleware.applies_to("/api/users/1"));
+        assert!(!middleware.applies_to("/apis"));
+        assert!(!middleware.applies_to("/"));
+
+        let root = Middleware {
+            path: String::new(),
+            ..middleware
+        };
+        assert!(root.applies_to("/"));
+        assert!(root.applies_to("/api/users/1"));
+    }
+}
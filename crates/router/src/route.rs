@@ -13,13 +13,13 @@ use segment::Segment;
 use std::{
     cmp::Ordering,
     cmp::Ordering::{Greater, Less},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     path::{Component, Path, PathBuf},
     sync::{Arc, RwLock},
 };
 use wws_config::Config as ProjectConfig;
-use wws_worker::Worker;
+use wws_worker::{errors::WorkerError, Worker};
 
 lazy_static! {
     static ref PARAMETER_REGEX: Regex =
@@ -27,6 +27,10 @@ lazy_static! {
     pub static ref WORKERS: RwLock<WorkerSet> = RwLock::new(WorkerSet::default());
 }
 
+/// HTTP methods that can be used as a suffix in the worker file names,
+/// like `[id].get.js`
+const METHOD_SUFFIXES: [&str; 7] = ["get", "post", "put", "patch", "delete", "head", "options"];
+
 /// An existing route in the project. It contains a reference to the handler, the URL path,
 /// the runner and configuration. Note that URL paths are calculated based on the file path.
 ///
@@ -36,6 +40,7 @@ lazy_static! {
 /// index.wasm          =>  /
 /// api/index.wasm      =>  /api
 /// api/v2/ping.wasm    =>  /api/v2/ping
+/// users/[id].get.js   =>  /users/[id] (GET only)
 /// ```
 #[derive(Clone, Debug)]
 pub struct Route {
@@ -49,6 +54,20 @@ pub struct Route {
     pub segments: Vec<Segment>,
     /// The associated worker
     pub worker: String,
+    /// The HTTP methods the route accepts. It accepts all of them when
+    /// it's not set
+    pub methods: Option<Vec<String>>,
+    /// Where the route comes from
+    pub source: RouteSource,
+}
+
+/// The origin of a route
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteSource {
+    /// The route path comes from the worker file path
+    File,
+    /// The route is declared in the routes manifest
+    Manifest,
 }
 
 /// Structure that holds the map of workers from their identifier to
@@ -71,6 +90,20 @@ impl WorkerSet {
     pub fn register(&mut self, worker_id: String, worker: Worker) {
         self.workers.insert(worker_id, Arc::new(worker));
     }
+
+    /// Remove the given worker. Requests that are already running it keep
+    /// their own reference until they finish.
+    pub fn remove(&mut self, worker_id: &str) {
+        self.workers.remove(worker_id);
+    }
+
+    /// Returns the cache keys of the compiled modules the workers use
+    pub fn cache_keys(&self) -> HashSet<&str> {
+        self.workers
+            .values()
+            .map(|worker| worker.cache_key())
+            .collect()
+    }
 }
 
 impl Route {
@@ -84,27 +117,74 @@ impl Route {
         prefix: &str,
         project_config: &ProjectConfig,
     ) -> Self {
-        let worker =
-            Worker::new(base_path, &filepath, project_config).expect("error creating worker");
-        let worker_id = worker.id.clone();
-
-        WORKERS
-            .write()
-            .expect("error locking worker lock for writing")
-            .register(worker_id.clone(), worker);
+        Self::try_new(base_path, filepath, prefix, project_config).expect("error creating worker")
+    }
+
+    /// Same as `new`, but it returns the error when the worker cannot be
+    /// initialized. It allows to skip invalid workers when reloading routes.
+    pub fn try_new(
+        base_path: &Path,
+        filepath: PathBuf,
+        prefix: &str,
+        project_config: &ProjectConfig,
+    ) -> Result<Self, WorkerError> {
         let route_path = Self::retrieve_route(base_path, &filepath, prefix);
-        Self {
+        let methods = Self::file_method(&filepath).map(|method| vec![method]);
+
+        Self::build(
+            base_path,
+            filepath,
+            route_path,
+            methods,
+            RouteSource::File,
+            project_config,
+        )
+    }
+
+    /// Initialize a route from the routes manifest. The URL path and
+    /// methods come from the manifest instead of the file path.
+    pub(crate) fn try_from_manifest(
+        base_path: &Path,
+        filepath: PathBuf,
+        route_path: String,
+        methods: Option<Vec<String>>,
+        project_config: &ProjectConfig,
+    ) -> Result<Self, WorkerError> {
+        Self::build(
+            base_path,
+            filepath,
+            route_path,
+            methods,
+            RouteSource::Manifest,
+            project_config,
+        )
+    }
+
+    /// Load the worker and register it
+    fn build(
+        base_path: &Path,
+        filepath: PathBuf,
+        route_path: String,
+        methods: Option<Vec<String>>,
+        source: RouteSource,
+        project_config: &ProjectConfig,
+    ) -> Result<Self, WorkerError> {
+        let worker_id = register_worker(base_path, &filepath, project_config)?;
+
+        Ok(Self {
             handler: filepath,
             route_type: RouteType::from(&route_path),
             segments: Self::get_segments(&route_path),
             path: route_path,
-            worker: worker_id.clone(),
-        }
+            worker: worker_id,
+            methods,
+            source,
+        })
     }
 
     // Process the given path to return the proper route for the API.
     // It will transform paths like test/index.wasm into /test.
-    fn retrieve_route(base_path: &Path, path: &Path, prefix: &str) -> String {
+    pub(crate) fn retrieve_route(base_path: &Path, path: &Path, prefix: &str) -> String {
         // Normalize both paths
         let n_path = Self::normalize_path_to_url(path);
         let n_base_path = Self::normalize_path_to_url(base_path);
@@ -128,13 +208,30 @@ impl Route {
         }
     }
 
-    // Prepare a path to be used as an URL. This method performs 3 main actions:
+    /// Returns the HTTP method in the file name of a worker, like `GET`
+    /// for `[id].get.js`
+    fn file_method(path: &Path) -> Option<String> {
+        let method = path.file_stem().map(Path::new)?.extension()?.to_str()?;
+
+        METHOD_SUFFIXES
+            .contains(&method)
+            .then(|| method.to_uppercase())
+    }
+
+    // Prepare a path to be used as an URL. This method performs 4 main actions:
     //
     // - Remove file extension
+    // - Remove the method suffix, like ".get"
     // - Keep only "normal" components. Others like "." or "./" are ignored
     // - Remove "index" components
     fn normalize_path_to_url(path: &Path) -> String {
-        path.with_extension("")
+        let mut url_path = path.with_extension("");
+
+        if Self::file_method(path).is_some() {
+            url_path.set_extension("");
+        }
+
+        url_path
             .components()
             .filter_map(|c| match c {
                 Component::Normal(os_str) if os_str != OsStr::new("index") => os_str
@@ -157,27 +254,35 @@ impl Route {
     /// to support parameters in the URLs.
     /// Dertermine the 'RouteType' allow to shortcut the comparaison.
     pub fn can_manage(&self, path: &str) -> bool {
-        let path_number_of_segments = path.chars().filter(|&c| c == '/').count();
+        path_matches(&self.path, &self.route_type, &self.segments, path)
+    }
 
-        match self.route_type {
-            Static {
-                number_of_segments: _,
-            } => self.path == path,
-            Dynamic { number_of_segments } if number_of_segments != path_number_of_segments => {
-                false
+    /// Check if the route accepts the given HTTP method. The routes that
+    /// accept `GET` requests accept the `HEAD` ones too
+    pub fn can_manage_method(&self, method: &str) -> bool {
+        match &self.methods {
+            Some(_) if method.eq_ignore_ascii_case("HEAD") => {
+                self.is_limited_to("HEAD") || self.is_limited_to("GET")
             }
-            Tail { number_of_segments } if number_of_segments > path_number_of_segments => false,
-            _ => path
-                .split('/')
-                .skip(1)
-                .zip(self.segments.iter())
-                .all(|zip| match zip {
-                    (sp, Segment::Static(segment)) => sp == segment,
-                    _ => true,
-                }),
+            Some(_) => self.is_limited_to(method),
+            None => true,
         }
     }
 
+    /// Check if the route lists the given HTTP method explicitly
+    pub fn is_limited_to(&self, method: &str) -> bool {
+        self.methods
+            .as_ref()
+            .is_some_and(|methods| methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+    }
+
+    /// Extract the parameters of the given path. For example, a `/[id]`
+    /// route returns `id => value` for the `/value` path. Tail segments
+    /// like `[...all]` get the rest of the path.
+    pub fn params(&self, path: &str) -> HashMap<String, String> {
+        path_params(&self.segments, path)
+    }
+
     /// Returns the given path with the actix format. For dynamic routing
     /// we are using `[]` in the filenames. However, actix expects a `{}`
     /// format for parameters.
@@ -203,9 +308,170 @@ impl Route {
     }
 }
 
+/// A URL path that may include parameters, like the source of a
+/// redirect. It matches the URLs in the same way as the routes.
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    /// The URL path
+    pub path: String,
+    route_type: RouteType,
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    pub fn new(path: &str) -> Self {
+        let path = path.to_string();
+
+        Self {
+            route_type: RouteType::from(&path),
+            segments: Route::get_segments(&path),
+            path,
+        }
+    }
+
+    /// Check if the given path matches the pattern
+    pub fn matches(&self, path: &str) -> bool {
+        path_matches(&self.path, &self.route_type, &self.segments, path)
+    }
+
+    /// Extract the parameters of the given path
+    pub fn params(&self, path: &str) -> HashMap<String, String> {
+        path_params(&self.segments, path)
+    }
+}
+
+/// Check if the given path matches the segments of a route path
+fn path_matches(
+    route_path: &str,
+    route_type: &RouteType,
+    segments: &[Segment],
+    path: &str,
+) -> bool {
+    let path_number_of_segments = path.chars().filter(|&c| c == '/').count();
+
+    match route_type {
+        Static {
+            number_of_segments: _,
+        } => route_path == path,
+        Dynamic { number_of_segments } if *number_of_segments != path_number_of_segments => false,
+        Tail { number_of_segments } if *number_of_segments > path_number_of_segments => false,
+        _ => path
+            .split('/')
+            .skip(1)
+            .zip(segments.iter())
+            .all(|zip| match zip {
+                (sp, Segment::Static(segment)) => sp == segment,
+                _ => true,
+            }),
+    }
+}
+
+/// Extract the parameters of the given path from the segments of a route path
+fn path_params(segments: &[Segment], path: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let path_segments: Vec<&str> = path.split('/').skip(1).collect();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let (segment, value) = match segment {
+            Segment::Static(_) => continue,
+            Segment::Dynamic(segment) => match path_segments.get(index) {
+                Some(value) => (segment, value.to_string()),
+                None => continue,
+            },
+            Segment::Tail(segment) => (
+                segment,
+                path_segments
+                    .get(index..)
+                    .map(|rest| rest.join("/"))
+                    .unwrap_or_default(),
+            ),
+        };
+
+        if let Some(caps) = PARAMETER_REGEX.captures(segment) {
+            let (name, parameter) = (&caps["segment"], caps.get(0).unwrap());
+            // Segments may include static text around the parameter
+            let value = value
+                .strip_prefix(&segment[..parameter.start()])
+                .and_then(|value| value.strip_suffix(&segment[parameter.end()..]))
+                .map(String::from)
+                .unwrap_or(value);
+
+            params.insert(name.to_string(), value);
+        }
+    }
+
+    params
+}
+
+/// Load the worker of the given file and register it. It returns the
+/// worker identifier
+pub(crate) fn register_worker(
+    base_path: &Path,
+    filepath: &Path,
+    project_config: &ProjectConfig,
+) -> Result<String, WorkerError> {
+    let worker = Worker::new(base_path, filepath, project_config)?;
+    let worker_id = worker.id.clone();
+
+    WORKERS
+        .write()
+        .expect("error locking worker lock for writing")
+        .register(worker_id.clone(), worker);
+
+    Ok(worker_id)
+}
+
+/// Returns the URL path of the folder that contains the given file. It's
+/// empty for the project root when there's no prefix
+pub(crate) fn folder_path(base_path: &Path, filepath: &Path, prefix: &str) -> String {
+    // The folder has the same path as an index file
+    Route::retrieve_route(base_path, &filepath.with_file_name("index"), prefix)
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Check if the given path is inside the folder with the given URL path
+pub(crate) fn folder_contains(folder: &str, path: &str) -> bool {
+    folder.is_empty()
+        || path == folder
+        || path
+            .strip_prefix(folder)
+            .map_or(false, |rest| rest.starts_with('/'))
+}
+
+/// Returns the names of the parameters in a route path
+pub(crate) fn param_names(route_path: &str) -> Vec<String> {
+    PARAMETER_REGEX
+        .captures_iter(route_path)
+        .map(|caps| caps["segment"].to_string())
+        .collect()
+}
+
+/// Replace the parameters in the given path with their values
+pub(crate) fn fill_params(route_path: &str, params: &HashMap<String, String>) -> String {
+    PARAMETER_REGEX
+        .replace_all(route_path, |caps: &regex::Captures| {
+            params.get(&caps["segment"]).cloned().unwrap_or_default()
+        })
+        .into()
+}
+
+/// Returns the route path without the parameter names. Two paths with the
+/// same shape, like `/users/[id]` and `/users/[name]`, handle the same URLs
+pub(crate) fn path_shape(route_path: &str) -> String {
+    PARAMETER_REGEX
+        .replace_all(route_path, |caps: &regex::Captures| {
+            match caps.name("ellipsis") {
+                Some(_) => "[...]",
+                None => "[]",
+            }
+        })
+        .into()
+}
+
 impl Ord for Route {
     fn cmp(&self, other: &Self) -> Ordering {
-        match (&self.route_type, &other.route_type) {
+        let ordering = match (&self.route_type, &other.route_type) {
             (
                 Static {
                     number_of_segments: a,
@@ -250,7 +516,11 @@ impl Ord for Route {
                     number_of_segments: b,
                 },
             ) => b.cmp(a),
-        }
+        };
+
+        // Routes limited to some methods go first. A `[id].get.js` file
+        // takes precedence over `[id].js` for the GET requests
+        ordering.then_with(|| self.methods.is_none().cmp(&other.methods.is_none()))
     }
 }
 
@@ -401,6 +671,11 @@ mod tests {
             ),
             ("./root/", "root/handler.js", "/handler"),
             ("./root/", "root/handler.wasm", "/handler"),
+            // Method suffixes
+            (".", "examples/handler.get.js", "/examples/handler"),
+            (".", "examples/[id].delete.wasm", "/examples/[id]"),
+            ("./root", "root/examples/index.post.js", "/examples"),
+            ("./root", "root/index.get.wasm", "/"),
         ];
 
         for t in tests {
@@ -475,4 +750,61 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn route_method_from_file_name() {
+        let tests = [
+            ("users/[id].get.js", Some("GET")),
+            ("users/[id].DELETE.wasm", None),
+            ("users/index.post.wasm", Some("POST")),
+            ("users/[id].js", None),
+            ("users/report.pdf.js", None),
+        ];
+
+        for (path, expected) in tests {
+            assert_eq!(
+                Route::file_method(Path::new(path)),
+                expected.map(String::from)
+            );
+        }
+    }
+
+    #[test]
+    fn route_params_extraction() {
+        let tests = [
+            ("/fixed", "/fixed", vec![]),
+            ("/[id]", "/any", vec![("id", "any")]),
+            ("/[id]/fixed", "/any/fixed", vec![("id", "any")]),
+            (
+                "/sub/[id]/[other]",
+                "/sub/a/b",
+                vec![("id", "a"), ("other", "b")],
+            ),
+            (
+                "/sub/[...all]",
+                "/sub/catch/all/routes",
+                vec![("all", "catch/all/routes")],
+            ),
+            ("/posts/post-[id]", "/posts/post-1", vec![("id", "1")]),
+        ];
+
+        for (route_path, path, expected) in tests {
+            let route = Route {
+                handler: PathBuf::new(),
+                path: route_path.to_string(),
+                route_type: RouteType::from(&route_path.to_string()),
+                segments: Route::get_segments(route_path),
+                worker: String::new(),
+                methods: None,
+                source: RouteSource::File,
+            };
+
+            let expected: HashMap<String, String> = expected
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+
+            assert_eq!(route.params(path), expected);
+        }
+    }
 }
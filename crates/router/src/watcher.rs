@@ -0,0 +1,105 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Routes, WORKERS};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use wws_config::Config;
+use wws_store::STORE_FOLDER;
+use wws_worker::cache;
+
+/// Time to wait for more events before updating the routes. Editors
+/// usually save a file in multiple steps, so the events are grouped.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watch the project files and update the given routes when a worker is
+/// added, changed or removed. The routes are updated in a copy and then
+/// replaced, so in-flight requests keep running with the previous state.
+///
+/// The watcher stops when the returned value is dropped.
+pub fn watch(
+    routes: Arc<RwLock<Routes>>,
+    path: PathBuf,
+    ignore_patterns: Vec<String>,
+    config: Config,
+) -> notify::Result<RecommendedWatcher> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(&path, RecursiveMode::Recursive)?;
+
+    thread::spawn(move || {
+        while let Ok(event) = receiver.recv() {
+            let mut changed_files = Vec::new();
+            collect_changed_files(event, &mut changed_files);
+
+            while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+                collect_changed_files(event, &mut changed_files);
+            }
+
+            if changed_files.is_empty() {
+                continue;
+            }
+
+            let mut updated_routes = routes
+                .read()
+                .expect("error locking routes lock for reading")
+                .clone();
+            let previous_workers = updated_routes.workers();
+            updated_routes.update(&path, ignore_patterns.clone(), &config, &changed_files);
+            let current_workers = updated_routes.workers();
+
+            *routes
+                .write()
+                .expect("error locking routes lock for writing") = updated_routes;
+
+            // The previous routes are not reachable anymore, so their
+            // workers can be removed now
+            remove_unused_workers(&path, &previous_workers, &current_workers);
+        }
+    });
+
+    Ok(watcher)
+}
+
+/// Remove the workers that the updated routes don't use anymore, and the
+/// compiled modules that are left without a worker. The lock is released
+/// before removing the files from the project store.
+fn remove_unused_workers(
+    path: &Path,
+    previous_workers: &HashSet<String>,
+    current_workers: &HashSet<String>,
+) {
+    let used_keys: HashSet<String> = {
+        let mut workers = WORKERS
+            .write()
+            .expect("error locking worker lock for writing");
+        for worker in previous_workers.difference(current_workers) {
+            workers.remove(worker);
+        }
+
+        workers.cache_keys().into_iter().map(String::from).collect()
+    };
+
+    cache::evict_unused(path, &used_keys.iter().map(String::as_str).collect());
+}
+
+/// Add the files from the given event. Changes in the project store are
+/// ignored, as wws writes on it while loading the workers.
+fn collect_changed_files(event: notify::Result<Event>, changed_files: &mut Vec<PathBuf>) {
+    match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+            changed_files.extend(event.paths.into_iter().filter(|path| {
+                !path
+                    .components()
+                    .any(|component| component.as_os_str() == OsStr::new(STORE_FOLDER))
+            }));
+        }
+        Ok(_) => {}
+        Err(err) => eprintln!("❌ Error watching the project files: {err}"),
+    }
+}
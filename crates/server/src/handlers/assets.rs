@@ -1,9 +1,9 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::AppData;
+use crate::hosts::find_site;
 use actix_files::NamedFile;
-use actix_web::{web::Data, HttpRequest};
+use actix_web::HttpRequest;
 use std::{
     io::{Error, ErrorKind},
     path::{Component, Path, PathBuf},
@@ -53,10 +53,7 @@ fn retrieve_asset_path(root_path: &Path, file_path: &Path, index_folder: bool) -
 ///
 /// If no file is present, it will try to get a default "public/404.html"
 pub async fn handle_assets(req: &HttpRequest) -> Result<NamedFile, Error> {
-    let root_path = &req
-        .app_data::<Data<AppData>>()
-        .expect("error fetching app data")
-        .root_path;
+    let root_path = &find_site(req).root_path;
     let uri_path = req.path();
 
     // Double-check the given path path does not contain any unexpected value.
@@ -0,0 +1,207 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wws_worker::io::WasmOutput;
+
+/// Header that tells the middlewares the current phase in the request. They
+/// set it to `next` in their response to continue with the chain.
+pub const MIDDLEWARE_HEADER: &str = "x-wws-middleware";
+/// Phase before running the route worker
+pub const BEFORE_PHASE: &str = "before";
+/// Phase after running the route worker. It only runs for the
+/// middlewares that request it
+pub const AFTER_PHASE: &str = "after";
+/// Value of the response header to continue with the chain
+const NEXT_VALUE: &str = "next";
+
+/// The changes a middleware applies to the request before continuing
+/// with the chain. A `None` value removes the header or parameter.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+pub struct Next {
+    #[serde(default)]
+    pub headers: HashMap<String, Option<String>>,
+    #[serde(default)]
+    pub params: HashMap<String, Option<String>>,
+    /// Run the middleware again after the route worker to
+    /// change the response headers
+    #[serde(default)]
+    pub after: bool,
+}
+
+/// The changes a middleware applies to the response headers in the
+/// after phase. A `None` value removes the header.
+#[derive(Deserialize, Debug, Default)]
+struct After {
+    #[serde(default)]
+    headers: HashMap<String, Option<String>>,
+}
+
+/// The response the middlewares receive as the request body in the after phase
+#[derive(Serialize)]
+struct ResponseSummary<'a> {
+    status: u16,
+    headers: &'a HashMap<String, Vec<String>>,
+}
+
+/// What a middleware decided about the request
+#[derive(Debug)]
+pub enum Decision {
+    /// Continue with the next middleware or the route worker
+    Next(Next),
+    /// Reply with the middleware response
+    Reply(WasmOutput),
+}
+
+/// Read the response of a middleware in the before phase. It continues
+/// with the chain when the response includes the middleware header. The
+/// body may include the changes to the request as JSON.
+pub fn read_decision(output: WasmOutput) -> Result<Decision, String> {
+    let next = output.headers.iter().any(|(key, values)| {
+        key.replace('_', "-")
+            .eq_ignore_ascii_case(MIDDLEWARE_HEADER)
+            && values.iter().any(|value| value == NEXT_VALUE)
+    });
+
+    if !next {
+        return Ok(Decision::Reply(output));
+    }
+
+    let body = output.body().map_err(|err| err.to_string())?;
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Decision::Next(Next::default()));
+    }
+
+    serde_json::from_slice(&body)
+        .map(Decision::Next)
+        .map_err(|err| err.to_string())
+}
+
+/// Apply the changes of a middleware to the request parameters
+pub fn apply_params(
+    params: &mut HashMap<String, String>,
+    changes: HashMap<String, Option<String>>,
+) {
+    for (key, value) in changes {
+        match value {
+            Some(value) => {
+                params.insert(key, value);
+            }
+            None => {
+                params.remove(&key);
+            }
+        }
+    }
+}
+
+/// Merge the changes of a middleware with the ones of the previous
+/// middlewares. Header names are case insensitive.
+pub fn apply_headers(
+    headers: &mut HashMap<String, Option<String>>,
+    changes: HashMap<String, Option<String>>,
+) {
+    for (key, value) in changes {
+        headers.insert(key.to_lowercase(), value);
+    }
+}
+
+/// Prepare the body of the request for the after phase
+pub fn after_body(output: &WasmOutput) -> Vec<u8> {
+    serde_json::to_vec(&ResponseSummary {
+        status: output.status,
+        headers: &output.headers,
+    })
+    .unwrap_or_default()
+}
+
+/// Apply the response of a middleware in the after phase to the
+/// headers of the final response
+pub fn apply_after(output: &mut WasmOutput, after: WasmOutput) -> Result<(), String> {
+    let body = after.body().map_err(|err| err.to_string())?;
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(());
+    }
+
+    let after: After = serde_json::from_slice(&body).map_err(|err| err.to_string())?;
+    for (key, value) in after.headers {
+        // Workers may set the headers with any case
+        output
+            .headers
+            .retain(|name, _| !name.replace('_', "-").eq_ignore_ascii_case(&key));
+
+        if let Some(value) = value {
+            output.headers.insert(key, vec![value]);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(body: &str, headers: &[(&str, &str)]) -> WasmOutput {
+        WasmOutput::new(
+            body,
+            headers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            200,
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn continue_with_the_changes() {
+        let decision = read_decision(output(
+            r#"{"headers": {"X-User": "1", "authorization": null}, "after": true}"#,
+            &[("X-WWS-Middleware", "next")],
+        ));
+
+        match decision {
+            Ok(Decision::Next(next)) => {
+                assert_eq!(next.headers.get("X-User"), Some(&Some("1".to_string())));
+                assert_eq!(next.headers.get("authorization"), Some(&None));
+                assert!(next.params.is_empty());
+                assert!(next.after);
+            }
+            _ => panic!("the middleware should continue"),
+        }
+
+        let decision = read_decision(output("", &[("x_wws_middleware", "next")]));
+        assert!(matches!(decision, Ok(Decision::Next(next)) if next == Next::default()));
+    }
+
+    #[test]
+    fn reply_without_the_header() {
+        let decision = read_decision(output("Unauthorized", &[]));
+
+        assert!(matches!(decision, Ok(Decision::Reply(_))));
+        assert!(read_decision(output("{", &[(MIDDLEWARE_HEADER, "next")])).is_err());
+    }
+
+    #[test]
+    fn rewrite_response_headers() {
+        let mut response = output("", &[("Content-Type", "text/plain"), ("Server", "wws")]);
+        let after = output(
+            r#"{"headers": {"content-type": "application/json", "server": null, "x-trace": "1"}}"#,
+            &[],
+        );
+
+        apply_after(&mut response, after).unwrap();
+
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some(&vec!["application/json".to_string()])
+        );
+        assert!(!response.headers.contains_key("Content-Type"));
+        assert!(!response.headers.contains_key("Server"));
+        assert_eq!(
+            response.headers.get("x-trace"),
+            Some(&vec!["1".to_string()])
+        );
+    }
+}
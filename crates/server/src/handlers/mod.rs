@@ -2,5 +2,6 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod assets;
+pub mod middleware;
 pub mod not_found;
 pub mod worker;
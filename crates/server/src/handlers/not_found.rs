@@ -1,17 +1,31 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::AppData;
+use super::assets::handle_assets;
+use super::worker::{run_error_worker, worker_response};
+use crate::hosts::find_site;
 use actix_files::NamedFile;
-use actix_web::{web::Data, HttpRequest, HttpResponse};
+use actix_web::{http::Method, http::StatusCode, web::Bytes, HttpRequest, HttpResponse};
+use wws_router::ErrorKind;
 
-/// This method tries to render a custom 404 error file from the static
-/// folder. If not, it will render an empty 404
-pub async fn handle_not_found(req: &HttpRequest) -> HttpResponse {
-    let root_path = &req
-        .app_data::<Data<AppData>>()
-        .expect("error fetching app data")
-        .root_path;
+/// This method runs the `_404` worker for the request path if there's any.
+/// Then, it tries to render a custom 404 error file from the static folder.
+/// If not, it will render an empty 404
+pub async fn handle_not_found(req: &HttpRequest, body: &[u8]) -> HttpResponse {
+    if let Some(output) = run_error_worker(
+        req,
+        body,
+        ErrorKind::NotFound,
+        req.path(),
+        StatusCode::NOT_FOUND.as_u16(),
+        "There's no route or asset for the request",
+    )
+    .await
+    {
+        return worker_response(req, output);
+    }
+
+    let root_path = &find_site(req).root_path;
     let public_404_path = root_path.join("public").join("404.html");
 
     if let Ok(file) = NamedFile::open_async(public_404_path).await {
@@ -20,3 +34,16 @@ pub async fn handle_not_found(req: &HttpRequest) -> HttpResponse {
         HttpResponse::NotFound().body("")
     }
 }
+
+/// Default service for the requests that don't match any route. It serves
+/// the static assets of the virtual hosts, as the main project is the only
+/// one with a static files service.
+pub async fn handle_default(req: HttpRequest, body: Bytes) -> HttpResponse {
+    if req.method() == Method::GET || req.method() == Method::HEAD {
+        if let Ok(existing_file) = handle_assets(&req).await {
+            return existing_file.into_response(&req);
+        }
+    }
+
+    handle_not_found(&req, &body).await
+}
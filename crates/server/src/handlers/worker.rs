@@ -1,25 +1,270 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{assets::handle_assets, not_found::handle_not_found};
-use crate::{AppData, DataConnectors};
+use super::{
+    assets::handle_assets,
+    middleware::{
+        after_body, apply_after, apply_headers, apply_params, read_decision, Decision, AFTER_PHASE,
+        BEFORE_PHASE, MIDDLEWARE_HEADER,
+    },
+    not_found::handle_not_found,
+};
+use crate::{hosts::find_site, AppData, DataConnectors, HttpMode};
 use actix_web::{
-    http::StatusCode,
+    http::{
+        header::{
+            HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS, ALLOW, LOCATION,
+        },
+        Method, StatusCode,
+    },
     web::{Bytes, Data},
     HttpRequest, HttpResponse,
 };
-use std::sync::RwLock;
-use wws_router::WORKERS;
-use wws_worker::io::WasmOutput;
+use std::{collections::HashMap, sync::Arc};
+use wws_data_kv::{errors::KVError, KVMode, KVStore};
+use wws_router::{ErrorKind, Middleware, RouteMatch, WORKERS};
+use wws_worker::{io::WasmOutput, Worker, WorkerData, WorkerRequest};
 
 const CORS_HEADER: &str = "Access-Control-Allow-Origin";
+/// Status code of the error that the error workers reply to
+const ERROR_STATUS_HEADER: &str = "x-wws-error-status";
+/// Details of the error that the error workers reply to
+const ERROR_MESSAGE_HEADER: &str = "x-wws-error-message";
+
+/// Reply with a redirect from the routes manifest. The location keeps
+/// the query of the request, unless it sets its own one.
+fn redirect_response(req: &HttpRequest, location: String, status: u16) -> HttpResponse {
+    let location = match req.query_string() {
+        query if query.is_empty() || location.contains('?') => location,
+        query => format!("{location}?{query}"),
+    };
+
+    HttpResponse::build(StatusCode::from_u16(status).unwrap_or(StatusCode::TEMPORARY_REDIRECT))
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+/// Reply to a method that none of the routes for the path accepts. The
+/// `Allow` header lists the methods they accept.
+fn method_not_allowed_response(allowed: Vec<String>) -> HttpResponse {
+    HttpResponse::MethodNotAllowed()
+        .insert_header((ALLOW, allowed.join(", ")))
+        .finish()
+}
+
+/// Reply to an `OPTIONS` request that none of the routes for the path
+/// accepts. It lists the allowed methods, so the CORS preflight requests
+/// succeed for the configured origins.
+fn options_response(req: &HttpRequest, allowed: Vec<String>) -> HttpResponse {
+    let app_data = req
+        .app_data::<Data<AppData>>()
+        .expect("error fetching app data");
+    let allowed = allowed.join(", ");
+    let mut builder = HttpResponse::NoContent();
+    builder.insert_header((ALLOW, allowed.clone()));
+
+    if let Some(origins) = app_data.cors_origins.as_ref() {
+        builder.insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, origins.join(",")));
+        builder.insert_header((ACCESS_CONTROL_ALLOW_METHODS, allowed));
+
+        if let Some(headers) = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS) {
+            builder.insert_header((ACCESS_CONTROL_ALLOW_HEADERS, headers.clone()));
+        }
+    }
+
+    builder.finish()
+}
+
+/// A middleware that runs again after the route worker. It receives the
+/// same headers and parameters as in the before phase
+struct AfterHook {
+    middleware: Middleware,
+    worker: Arc<Worker>,
+    headers: HashMap<String, Option<String>>,
+    params: HashMap<String, String>,
+}
+
+/// Returns the worker with the given identifier. Workers may be removed
+/// after selecting the route
+fn find_worker(worker_id: &str) -> Option<Arc<Worker>> {
+    WORKERS
+        .read()
+        .expect("error locking worker lock for reading")
+        .get(worker_id)
+        .cloned()
+}
+
+/// Set the middleware phase in the request headers. The route worker
+/// doesn't get any, so the clients cannot set it either
+fn with_phase(
+    headers: &HashMap<String, Option<String>>,
+    phase: Option<&str>,
+) -> HashMap<String, Option<String>> {
+    let mut headers = headers.clone();
+    headers.insert(MIDDLEWARE_HEADER.to_string(), phase.map(String::from));
+
+    headers
+}
+
+/// Run the worker with its data connectors and write back the K/V changes.
+/// Errors loading or saving the data reply to the client directly.
+async fn run_worker(
+    worker: &Worker,
+    request: WorkerRequest<'_>,
+    data_connectors: &DataConnectors,
+    http_mode: &HttpMode,
+    route_path: &str,
+) -> Result<WasmOutput, HttpResponse> {
+    // Init from configuration
+    let vars = &worker.config.vars;
+    let kv_config = worker.config.data_kv_config();
+
+    let kv_store = match kv_config {
+        Some(kv) => {
+            // Workers loaded while the server is running may use new namespaces
+            if let Err(err) = data_connectors.kv.create_store(kv) {
+                eprintln!("Error loading the K/V store {}: {err}", kv.namespace);
+
+                return Err(HttpResponse::InternalServerError()
+                    .body("There was an error loading the worker data"));
+            }
+
+            data_connectors.kv.find_store(&kv.namespace)
+        }
+        None => None,
+    };
+
+    // In snapshot mode, the worker receives the whole namespace and returns
+    // the new state. In host mode, it accesses the keys through host functions
+    let (snapshot_store, host_store) = match kv_config.map(|kv| kv.mode) {
+        Some(KVMode::Host) => (None, kv_store),
+        _ => (kv_store, None),
+    };
+    let snapshot = snapshot_store.as_deref().map(KVStore::snapshot);
+
+    // The worker can only access the databases listed in its configuration
+    let databases = match worker.config.data_sql_config() {
+        Some(sql) => match data_connectors.sql.open_databases(sql) {
+            Ok(databases) => databases,
+            Err(err) => {
+                eprintln!("Error loading the SQL databases: {err}");
+
+                return Err(HttpResponse::InternalServerError()
+                    .body("There was an error loading the worker data"));
+            }
+        },
+        None => HashMap::new(),
+    };
+
+    let (handler_result, handler_success) = match worker
+        .run(
+            request,
+            WorkerData {
+                kv: snapshot.as_ref().map(|snapshot| snapshot.data.clone()),
+                kv_store: host_store,
+                databases,
+            },
+            vars,
+            http_mode,
+        )
+        .await
+    {
+        Ok(output) => (output, true),
+        Err(err) => (
+            WasmOutput::failed(
+                err,
+                worker.config.name.clone(),
+                Some(route_path.to_string()),
+            ),
+            false,
+        ),
+    };
+
+    // Write to the state if required. Only the keys the worker changed are
+    // written back, so concurrent requests don't override each other
+    if handler_success {
+        if let (Some(store), Some(snapshot), Some(kv)) = (snapshot_store, snapshot, kv_config) {
+            match store.write_back(&snapshot, &handler_result.kv, kv.on_conflict) {
+                Ok(conflicts) if !conflicts.is_empty() => {
+                    eprintln!(
+                        "Concurrent changes in the K/V store {} for the keys: {}",
+                        store.namespace,
+                        conflicts.join(", ")
+                    );
+                }
+                Ok(_) => {}
+                Err(err @ KVError::Conflict { .. }) => {
+                    return Err(HttpResponse::Conflict().body(err.to_string()));
+                }
+                Err(err @ KVError::QuotaExceeded { .. }) => {
+                    return Err(HttpResponse::InsufficientStorage().body(err.to_string()));
+                }
+                Err(err) => {
+                    eprintln!("Error saving the K/V store {}: {err}", store.namespace);
+                }
+            }
+        }
+    }
+
+    Ok(handler_result)
+}
+
+/// Run the error worker of the given kind for the path. It receives the
+/// original request and the error details in the `x-wws-error-status` and
+/// `x-wws-error-message` headers. It returns `None` when there's no error
+/// worker or it fails too.
+pub async fn run_error_worker(
+    req: &HttpRequest,
+    body: &[u8],
+    kind: ErrorKind,
+    path: &str,
+    status: u16,
+    message: &str,
+) -> Option<WasmOutput> {
+    let app_data = req
+        .app_data::<Data<AppData>>()
+        .expect("error fetching app data");
+    let site = find_site(req);
+
+    let error_worker = site
+        .routes
+        .read()
+        .expect("error locking routes lock for reading")
+        .error_worker_for(kind, path)?;
+    let worker = find_worker(&error_worker.worker)?;
+
+    let request = WorkerRequest {
+        request: req,
+        body,
+        params: HashMap::new(),
+        headers: HashMap::from([
+            (ERROR_STATUS_HEADER.to_string(), Some(status.to_string())),
+            (ERROR_MESSAGE_HEADER.to_string(), Some(message.to_string())),
+            (MIDDLEWARE_HEADER.to_string(), None),
+        ]),
+    };
+
+    match run_worker(
+        &worker,
+        request,
+        &site.data,
+        &app_data.http_mode,
+        &error_worker.path,
+    )
+    .await
+    {
+        Ok(output) if output.error.is_none() => Some(output),
+        _ => None,
+    }
+}
 
 /// Process an HTTP request by passing it to the right Runner. The Runner
 /// will prepare the WASI environment and call the Wasm module with the data.
 ///
-/// Note that here we have to select the runner by checking the path. This
-/// responsibility is duplicated as Actix already maps paths to handlers.
-/// However, there are several reasons why this is reasonable in this project:
+/// Note that here we have to select the runner by checking the path. Actix
+/// forwards all the requests that match a route to this handler. There are
+/// several reasons why this is reasonable in this project:
 ///
 /// - We fully control collisions when a request can be served by a parametrized
 ///   route. Actix will reply with the first handler that matches. However, users
@@ -28,6 +273,7 @@ const CORS_HEADER: &str = "Access-Control-Allow-Origin";
 ///   that Actix will create an instance per thread (worker), so Runners cannot be
 ///   shared. This will require multiple instances of the same Wasm module, so
 ///   the resource consumption will be increased.
+/// - Routes can change while the server is running in watch mode.
 ///
 /// For these reasons, we are selecting the right handler at this point and not
 /// allowing Actix to select it for us.
@@ -35,104 +281,238 @@ pub async fn handle_worker(req: HttpRequest, body: Bytes) -> HttpResponse {
     let app_data = req
         .app_data::<Data<AppData>>()
         .expect("error fetching app data");
-    let data_connectors = req
-        .app_data::<Data<RwLock<DataConnectors>>>()
-        .expect("error fetching data connectors");
-
-    // First, we need to identify the best suited route
-    let selected_route = app_data.routes.retrieve_best_route(req.path());
-    let worker = if let Some(route) = selected_route {
-        // First, check if there's an existing static file. Static assets have more priority
-        // than dynamic routes. However, I cannot set the static assets as the first service
-        // as it's captures everything.
-        if route.is_dynamic() {
-            if let Ok(existing_file) = handle_assets(&req).await {
-                return existing_file.into_response(&req);
-            }
-        }
+    // Each host may have its own project
+    let site = find_site(&req);
+    let data_connectors = &site.data;
 
-        let workers = WORKERS
+    // First, we need to identify the best suited route and its middlewares. The
+    // routes may change while the request is running, so it keeps its own copy
+    let (route_match, middlewares) = {
+        let routes = site
+            .routes
             .read()
-            .expect("error locking worker lock for reading");
+            .expect("error locking routes lock for reading");
+        let route_match = routes.resolve(req.method().as_str(), req.path());
+        let middlewares = match &route_match {
+            Some(RouteMatch::Worker { path, .. }) => routes.middlewares_for(path),
+            _ => Vec::new(),
+        };
 
-        Some(
-            workers
-                .get(&route.worker)
-                .expect("unexpected missing worker")
-                .clone(),
-        )
-    } else {
-        None
+        (route_match, middlewares)
+    };
+    let (route, path) = match route_match {
+        Some(RouteMatch::Redirect { location, status }) => {
+            return redirect_response(&req, location, status);
+        }
+        Some(RouteMatch::Worker { route, path }) => (route, path),
+        Some(RouteMatch::MethodNotAllowed { allowed }) if req.method() == Method::OPTIONS => {
+            return options_response(&req, allowed);
+        }
+        Some(RouteMatch::MethodNotAllowed { allowed }) => {
+            return method_not_allowed_response(allowed);
+        }
+        None => return handle_not_found(&req, &body).await,
     };
 
-    if worker.is_none() {
-        return handle_not_found(&req).await;
+    // First, check if there's an existing static file. Static assets have more priority
+    // than dynamic routes. However, I cannot set the static assets as the first service
+    // as it's captures everything.
+    if route.is_dynamic() {
+        if let Ok(existing_file) = handle_assets(&req).await {
+            return existing_file.into_response(&req);
+        }
+    }
+
+    // The worker may be removed after selecting the route
+    let Some(worker) = find_worker(&route.worker) else {
+        return handle_not_found(&req, &body).await;
     };
-    let worker = worker.unwrap();
 
-    let body_str = String::from_utf8(body.to_vec()).unwrap_or_else(|_| String::from(""));
+    let mut params = route.params(&path);
+    let mut headers = HashMap::new();
+    let mut after_hooks = Vec::new();
+    let mut reply = None;
 
-    // Init from configuration
-    let vars = &worker.config.vars;
-    let kv_namespace = worker.config.data_kv_namespace();
+    // Middlewares run from the outer folder to the inner one. Any of them
+    // can reply before reaching the route worker
+    for middleware in middlewares {
+        let Some(middleware_worker) = find_worker(&middleware.worker) else {
+            continue;
+        };
+        let request = WorkerRequest {
+            request: &req,
+            body: &body,
+            params: params.clone(),
+            headers: with_phase(&headers, Some(BEFORE_PHASE)),
+        };
+        let output = match run_worker(
+            &middleware_worker,
+            request,
+            data_connectors,
+            &app_data.http_mode,
+            &middleware.path,
+        )
+        .await
+        {
+            Ok(output) => output,
+            Err(response) => return response,
+        };
 
-    let store = match &kv_namespace {
-        Some(namespace) => {
-            let connector = data_connectors
-                .read()
-                .expect("error locking data connectors lock for reading");
-            let kv_store = connector.kv.find_store(namespace);
+        match read_decision(output) {
+            Ok(Decision::Next(next)) => {
+                apply_headers(&mut headers, next.headers);
+                apply_params(&mut params, next.params);
 
-            kv_store.map(|store| store.clone())
+                if next.after {
+                    after_hooks.push(AfterHook {
+                        middleware,
+                        worker: middleware_worker,
+                        headers: headers.clone(),
+                        params: params.clone(),
+                    });
+                }
+            }
+            Ok(Decision::Reply(output)) => {
+                reply = Some(output);
+                break;
+            }
+            Err(err) => {
+                eprintln!(
+                    "Error reading the response of the middleware {}: {err}",
+                    middleware.handler.display()
+                );
+
+                return HttpResponse::InternalServerError()
+                    .body("There was an error running the middleware");
+            }
         }
-        None => None,
-    };
+    }
 
-    let (handler_result, handler_success) = match worker.run(&req, &body_str, store, vars).await {
-        Ok(output) => (output, true),
-        Err(err) => (
-            WasmOutput::failed(
-                err,
-                worker.config.name.clone(),
-                selected_route.map(|route| route.path.clone()),
-            ),
-            false,
-        ),
+    let mut handler_result = match reply {
+        Some(output) => output,
+        None => {
+            let request = WorkerRequest {
+                request: &req,
+                body: &body,
+                params,
+                headers: with_phase(&headers, None),
+            };
+
+            match run_worker(
+                &worker,
+                request,
+                data_connectors,
+                &app_data.http_mode,
+                &route.path,
+            )
+            .await
+            {
+                Ok(output) => output,
+                Err(response) => return response,
+            }
+        }
     };
 
+    // Failed workers reply with the error worker of the route, if any
+    if let Some(error) = handler_result.error.clone() {
+        if let Some(output) = run_error_worker(
+            &req,
+            &body,
+            ErrorKind::Error,
+            &path,
+            handler_result.status,
+            &error,
+        )
+        .await
+        {
+            handler_result = output;
+        }
+    }
+
+    // The after phase runs from the inner middleware to the outer one. The
+    // middlewares receive the response status and headers as the body
+    for hook in after_hooks.into_iter().rev() {
+        let body = after_body(&handler_result);
+        let request = WorkerRequest {
+            request: &req,
+            body: &body,
+            params: hook.params,
+            headers: with_phase(&hook.headers, Some(AFTER_PHASE)),
+        };
+
+        match run_worker(
+            &hook.worker,
+            request,
+            data_connectors,
+            &app_data.http_mode,
+            &hook.middleware.path,
+        )
+        .await
+        {
+            Ok(output) => {
+                if let Err(err) = apply_after(&mut handler_result, output) {
+                    eprintln!(
+                        "Error reading the response of the middleware {}: {err}",
+                        hook.middleware.handler.display()
+                    );
+                }
+            }
+            Err(response) => return response,
+        }
+    }
+
+    worker_response(&req, handler_result)
+}
+
+/// Build the HTTP response from the output of a worker. It sets the
+/// default content type and the CORS origins.
+pub fn worker_response(req: &HttpRequest, output: WasmOutput) -> HttpResponse {
+    let app_data = req
+        .app_data::<Data<AppData>>()
+        .expect("error fetching app data");
     let mut builder =
-        HttpResponse::build(StatusCode::from_u16(handler_result.status).unwrap_or(StatusCode::OK));
+        HttpResponse::build(StatusCode::from_u16(output.status).unwrap_or(StatusCode::OK));
     // Default content type
     builder.insert_header(("Content-Type", "text/html"));
 
     // Check if cors config has any origins to register
     if let Some(origins) = app_data.cors_origins.as_ref() {
         // Check if worker has overridden the header, if not
-        if !handler_result.headers.contains_key(CORS_HEADER) {
+        if !output.headers.contains_key(CORS_HEADER) {
             // insert those origins in 'Access-Control-Allow-Origin' header
             let header_value = origins.join(",");
             builder.insert_header((CORS_HEADER, header_value));
         }
     }
 
-    for (key, val) in handler_result.headers.iter() {
+    for (key, values) in output.headers.iter() {
         // Note that QuickJS is replacing the "-" character
         // with "_" on property keys. Here, we rollback it
-        builder.insert_header((key.replace('_', "-").as_str(), val.as_str()));
-    }
+        let Ok(name) = HeaderName::try_from(key.replace('_', "-")) else {
+            eprintln!("Ignoring invalid header name {key:?} from worker");
+            continue;
+        };
+        let mut values = values
+            .iter()
+            .filter_map(|value| match HeaderValue::try_from(value) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    eprintln!("Ignoring invalid value {value:?} for header {name} from worker");
+                    None
+                }
+            });
 
-    // Write to the state if required
-    if handler_success {
-        if let Some(kv_namespace) = kv_namespace {
-            data_connectors
-                .write()
-                .expect("error locking data connectors lock for writing")
-                .kv
-                .replace_store(&kv_namespace, &handler_result.kv)
+        // The first value replaces the default ones, like the content type
+        if let Some(value) = values.next() {
+            builder.insert_header((name.clone(), value));
+        }
+
+        for value in values {
+            builder.append_header((name.clone(), value));
         }
     }
 
-    match handler_result.body() {
+    match output.body() {
         Ok(res) => builder.body(res),
         Err(_) => HttpResponse::ServiceUnavailable().body("There was an error running the worker"),
     }
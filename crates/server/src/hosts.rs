@@ -0,0 +1,189 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::DataConnectors;
+use actix_web::{dev::RequestHead, http::header::HOST, web::Data, HttpRequest};
+use std::{
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use wws_data_kv::KVBackend;
+use wws_router::Routes;
+
+/// A host name of the requests. A `*.` prefix matches any subdomain, like
+/// `*.example.com` for `blog.example.com`. Host names are case insensitive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPattern {
+    /// A single host name
+    Exact(String),
+    /// Any subdomain of the host name. It keeps the leading dot
+    Wildcard(String),
+}
+
+impl HostPattern {
+    /// Check if the pattern matches the given host name
+    pub fn matches(&self, host: &str) -> bool {
+        let host = normalize_host(host);
+
+        match self {
+            Self::Exact(name) => host == *name,
+            Self::Wildcard(suffix) => host.len() > suffix.len() && host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let host = normalize_host(value);
+        let (pattern, name) = match host.strip_prefix("*.") {
+            Some(name) => (Self::Wildcard(format!(".{name}")), name),
+            None => (Self::Exact(host.clone()), host.as_str()),
+        };
+
+        let valid = !name.is_empty()
+            && name.split('.').all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+
+        if valid {
+            Ok(pattern)
+        } else {
+            Err(format!("The host {value} is not valid"))
+        }
+    }
+}
+
+impl fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Exact(name) => write!(f, "{name}"),
+            Self::Wildcard(suffix) => write!(f, "*{suffix}"),
+        }
+    }
+}
+
+/// Host names ignore the case and the trailing dot
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_lowercase()
+}
+
+/// Returns the host name of the request without the port. It comes
+/// from the `Host` header or the request URI
+fn request_host(head: &RequestHead) -> Option<&str> {
+    let host = head
+        .headers
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| head.uri.host())?;
+
+    // IPv6 addresses use brackets to separate the port
+    if host.starts_with('[') {
+        return host.split_inclusive(']').next();
+    }
+
+    Some(host.split(':').next().unwrap_or(host))
+}
+
+/// A project that serves the requests for the matching hosts
+#[derive(Clone)]
+pub struct VirtualHost {
+    pub host: HostPattern,
+    pub root_path: PathBuf,
+    /// Routes shared with the server. They can change while it's running
+    pub routes: Arc<RwLock<Routes>>,
+    /// Storage backend for the K/V namespaces that don't configure one
+    pub kv_backend: KVBackend,
+}
+
+/// A project served by the server. Each one has its own routes,
+/// public folder and data connectors
+pub(crate) struct Site {
+    /// The hosts of the site. It's not present for the main project
+    pub host: Option<HostPattern>,
+    pub root_path: PathBuf,
+    pub routes: Arc<RwLock<Routes>>,
+    pub data: DataConnectors,
+}
+
+/// The projects served by the server
+pub(crate) struct Sites {
+    /// The main project. It serves the requests that don't match any host
+    pub main: Site,
+    /// The virtual hosts. Single host names go before the wildcards
+    pub hosts: Vec<Site>,
+}
+
+impl Sites {
+    pub fn new(main: Site, mut hosts: Vec<Site>) -> Self {
+        hosts.sort_by_key(|site| matches!(site.host, Some(HostPattern::Wildcard(_))));
+
+        Self { main, hosts }
+    }
+
+    /// Returns the site that serves the given request
+    pub fn find(&self, head: &RequestHead) -> &Site {
+        request_host(head)
+            .and_then(|host| {
+                self.hosts.iter().find(|site| {
+                    site.host
+                        .as_ref()
+                        .map_or(false, |pattern| pattern.matches(host))
+                })
+            })
+            .unwrap_or(&self.main)
+    }
+
+    /// Check if the main project serves the given request
+    pub fn is_main(&self, head: &RequestHead) -> bool {
+        self.find(head).host.is_none()
+    }
+}
+
+/// Returns the site that serves the given request
+pub(crate) fn find_site(req: &HttpRequest) -> &Site {
+    req.app_data::<Data<Sites>>()
+        .expect("error fetching sites")
+        .find(req.head())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_host_patterns() {
+        let exact: HostPattern = "Blog.Example.com".parse().unwrap();
+        let wildcard: HostPattern = "*.example.com".parse().unwrap();
+
+        assert!(exact.matches("blog.example.com"));
+        assert!(exact.matches("blog.example.com."));
+        assert!(!exact.matches("www.blog.example.com"));
+
+        assert!(wildcard.matches("blog.example.com"));
+        assert!(wildcard.matches("a.b.example.com"));
+        assert!(!wildcard.matches("example.com"));
+        assert!(!wildcard.matches("badexample.com"));
+
+        assert!("".parse::<HostPattern>().is_err());
+        assert!("*.".parse::<HostPattern>().is_err());
+        assert!("api.*.com".parse::<HostPattern>().is_err());
+        assert!("example.com:8080".parse::<HostPattern>().is_err());
+    }
+
+    #[test]
+    fn read_request_host() {
+        let mut head = RequestHead::default();
+        assert_eq!(request_host(&head), None);
+
+        head.headers
+            .insert(HOST, "blog.example.com:8080".parse().unwrap());
+        assert_eq!(request_host(&head), Some("blog.example.com"));
+
+        head.headers.insert(HOST, "[::1]:8080".parse().unwrap());
+        assert_eq!(request_host(&head), Some("[::1]"));
+    }
+}
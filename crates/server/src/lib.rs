@@ -5,23 +5,32 @@ mod errors;
 use errors::{Result, ServeError};
 
 mod handlers;
+mod hosts;
 
 use actix_files::Files;
 use actix_web::dev::{fn_service, Server, ServiceRequest, ServiceResponse};
 use actix_web::{
-    middleware,
+    guard, middleware,
     web::{self, Data},
     App, HttpServer,
 };
 use handlers::assets::handle_assets;
-use handlers::not_found::handle_not_found;
+use handlers::not_found::{handle_default, handle_not_found};
 use handlers::worker::handle_worker;
-use std::{path::PathBuf, sync::RwLock};
+use hosts::{Site, Sites};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 use wws_api_manage::config_manage_api_handlers;
-use wws_data_kv::KV;
+use wws_data_kv::{KVBackend, KV};
+use wws_data_sql::SQL;
 use wws_panel::config_panel_handlers;
 use wws_router::{Routes, WORKERS};
 
+pub use hosts::{HostPattern, VirtualHost};
+pub use wws_worker::fixtures::HttpMode;
+
 #[derive(Clone, PartialEq)]
 pub enum Panel {
     Enabled,
@@ -38,34 +47,95 @@ impl From<bool> for Panel {
     }
 }
 
-#[derive(Default)]
+/// The data connectors shared by all the workers. They synchronize
+/// the access to their data internally
 pub(crate) struct DataConnectors {
-    kv: KV,
+    kv: Arc<KV>,
+    sql: SQL,
 }
 
 #[derive(Clone)]
 pub struct ServeOptions {
     pub root_path: PathBuf,
-    pub base_routes: Routes,
+    /// Routes shared with the server. They can change while it's running
+    pub base_routes: Arc<RwLock<Routes>>,
     pub hostname: String,
     pub port: u16,
     pub panel: Panel,
     pub cors_origins: Option<Vec<String>>,
+    /// Storage backend for the K/V namespaces that don't configure one
+    pub kv_backend: KVBackend,
+    /// Send the HTTP requests of the workers to the network or use fixtures
+    pub http_mode: HttpMode,
+    /// Other projects to serve based on the request host. The
+    /// rest of the requests go to the main project
+    pub hosts: Vec<VirtualHost>,
 }
 
 #[derive(Default)]
 pub struct AppData {
-    routes: Routes,
-    root_path: PathBuf,
     cors_origins: Option<Vec<String>>,
+    http_mode: HttpMode,
 }
 
 impl From<ServeOptions> for AppData {
     fn from(serve_options: ServeOptions) -> Self {
         AppData {
-            routes: serve_options.base_routes,
-            root_path: serve_options.root_path.clone(),
             cors_origins: serve_options.cors_origins.clone(),
+            http_mode: serve_options.http_mode,
+        }
+    }
+}
+
+/// Initializes a project served by the server and its data connectors
+fn site(
+    host: Option<HostPattern>,
+    root_path: &Path,
+    routes: &Arc<RwLock<Routes>>,
+    kv_backend: KVBackend,
+) -> Site {
+    let kv = KV::new(root_path.to_path_buf(), kv_backend);
+    create_kv_stores(&kv, routes);
+
+    Site {
+        host,
+        root_path: root_path.to_path_buf(),
+        routes: Arc::clone(routes),
+        data: DataConnectors {
+            kv: Arc::new(kv),
+            sql: SQL::new(root_path.to_path_buf()),
+        },
+    }
+}
+
+/// Create the K/V stores of the current workers, so the management API
+/// can access them before any worker runs. The workers loaded later
+/// create their stores on the first request.
+fn create_kv_stores(kv: &KV, routes: &RwLock<Routes>) {
+    let routes = routes
+        .read()
+        .expect("error locking routes lock for reading");
+    let workers = WORKERS
+        .read()
+        .expect("error locking worker lock for reading");
+
+    let worker_ids = routes
+        .routes
+        .iter()
+        .map(|route| &route.worker)
+        .chain(routes.middlewares.iter().map(|m| &m.worker))
+        .chain(routes.error_workers.iter().map(|e| &e.worker));
+
+    for worker_id in worker_ids {
+        let Some(kv_config) = workers
+            .get(worker_id)
+            .and_then(|worker| worker.config.data_kv_config())
+        else {
+            continue;
+        };
+
+        if let Err(err) = kv.create_store(kv_config) {
+            eprintln!("Error loading the K/V store {}: {err}", kv_config.namespace);
         }
     }
 }
@@ -74,8 +144,28 @@ impl From<ServeOptions> for AppData {
 /// path. It will configure the different handlers to manage static
 /// assets and workers.
 pub async fn serve(serve_options: ServeOptions) -> Result<Server> {
-    // Initializes the data connectors. For now, just KV
-    let data_connectors = Data::new(RwLock::new(DataConnectors::default()));
+    // Initializes the projects and their data connectors
+    let hosts = serve_options
+        .hosts
+        .iter()
+        .map(|host| {
+            site(
+                Some(host.host.clone()),
+                &host.root_path,
+                &host.routes,
+                host.kv_backend,
+            )
+        })
+        .collect();
+    let sites = Data::new(Sites::new(
+        site(
+            None,
+            &serve_options.root_path,
+            &serve_options.base_routes,
+            serve_options.kv_backend,
+        ),
+        hosts,
+    ));
 
     let (hostname, port) = (serve_options.hostname.clone(), serve_options.port);
     let serve_options = serve_options.clone();
@@ -83,7 +173,8 @@ pub async fn serve(serve_options: ServeOptions) -> Result<Server> {
     let server = HttpServer::new(move || {
         // Initializes the app data for handlers
         let app_data: Data<AppData> = Data::new(serve_options.clone().into());
-        let routes_data: Data<Routes> = Data::new(app_data.routes.clone());
+        // The panel and the management API only manage the main project
+        let routes_data: Data<RwLock<Routes>> = Data::from(Arc::clone(&sites.main.routes));
 
         let mut app = App::new()
             // enable logger
@@ -92,46 +183,70 @@ pub async fn serve(serve_options: ServeOptions) -> Result<Server> {
             .wrap(middleware::NormalizePath::trim())
             .app_data(Data::clone(&app_data))
             .app_data(Data::clone(&routes_data))
-            .app_data(Data::clone(&data_connectors));
+            .app_data(Data::clone(&sites))
+            // The management API can inspect and edit the K/V stores
+            .app_data(Data::from(Arc::clone(&sites.main.data.kv)));
+
+        // Workers can be added or removed while the server is running, so
+        // a single resource checks the current routes on every request.
+        // The panel only belongs to the main project, so the workers of the
+        // virtual hosts go before it
+        let worker_resource = |main: bool| {
+            let guard_sites = Data::clone(&sites);
+
+            web::resource("/{tail:.*}")
+                .guard(guard::fn_guard(move |ctx| {
+                    guard_sites.is_main(ctx.head()) == main
+                        && guard_sites
+                            .find(ctx.head())
+                            .routes
+                            .read()
+                            .expect("error locking routes lock for reading")
+                            .resolve(ctx.head().method.as_str(), ctx.head().uri.path())
+                            .is_some()
+                }))
+                .to(handle_worker)
+        };
+        app = app.service(worker_resource(false));
 
         // Configure panel
         if serve_options.panel == Panel::Enabled {
+            // The virtual hosts reply to these paths like to any other
+            // missing route, instead of exposing the main project
+            for path in ["/_panel{tail:.*}", "/_api/{tail:.*}"] {
+                let host_sites = Data::clone(&sites);
+                app = app.service(
+                    web::resource(path)
+                        .guard(guard::fn_guard(move |ctx| !host_sites.is_main(ctx.head())))
+                        .to(handle_default),
+                );
+            }
+
             app = app.configure(config_panel_handlers);
             app = app.configure(config_manage_api_handlers);
         }
 
-        let workers = WORKERS
-            .read()
-            .expect("error locking worker lock for reading");
-
-        // Append routes to the current service
-        for route in app_data.routes.iter() {
-            app = app.service(web::resource(route.actix_path()).to(handle_worker));
-
-            let worker = workers
-                .get(&route.worker)
-                .expect("unexpected missing worker");
-
-            // Configure KV
-            if let Some(namespace) = worker.config.data_kv_namespace() {
-                data_connectors
-                    .write()
-                    .expect("cannot retrieve shared data")
-                    .kv
-                    .create_store(&namespace);
-            }
-        }
+        app = app.service(worker_resource(true));
 
-        // Serve static files from the static folder
-        let mut static_prefix = app_data.routes.prefix.clone();
+        // Serve static files from the static folder of the main project. The
+        // default service serves the assets of the virtual hosts
+        let mut static_prefix = sites
+            .main
+            .routes
+            .read()
+            .expect("error locking routes lock for reading")
+            .prefix
+            .clone();
         if static_prefix.is_empty() {
             static_prefix = String::from("/");
         }
 
-        let public_dir = app_data.root_path.join("public");
+        let public_dir = sites.main.root_path.join("public");
         if public_dir.exists() {
+            let main_sites = Data::clone(&sites);
             app = app.service(
                 Files::new(&static_prefix, public_dir)
+                    .guard(guard::fn_guard(move |ctx| main_sites.is_main(ctx.head())))
                     .index_file("index.html")
                     // This handler check if there's an HTML file in the public folder that
                     // can reply to the given request. For example, if someone request /about,
@@ -145,7 +260,7 @@ pub async fn serve(serve_options: ServeOptions) -> Result<Server> {
                                 Ok(ServiceResponse::new(req, res))
                             }
                             Err(_) => {
-                                let res = handle_not_found(&req).await;
+                                let res = handle_not_found(&req, &[]).await;
                                 Ok(ServiceResponse::new(req, res))
                             }
                         }
@@ -153,6 +268,9 @@ pub async fn serve(serve_options: ServeOptions) -> Result<Server> {
             );
         }
 
+        // The requests without a route or asset may have a custom reply
+        app = app.default_service(web::to(handle_default));
+
         app
     })
     .bind(format!("{}:{}", hostname, port))
@@ -1,19 +1,61 @@
 // Copyright 2023 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::features::http_requests::HttpRequestsConfig;
-use actix_web::http::Uri;
-use reqwest::Method;
-use tokio::runtime::Builder;
+use crate::features::http_requests::{AddressPolicy, HttpRequestsConfig};
+use crate::fixtures::{load_fixture, save_fixture, HttpMode, RecordedRequest, RecordedResponse};
+use lazy_static::lazy_static;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{AUTHORIZATION, COOKIE, LOCATION, PROXY_AUTHORIZATION},
+    redirect::Policy,
+    Certificate, Identity, Method, NoProxy, Proxy, StatusCode, Url,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::{net::lookup_host, time::timeout};
+use wws_config::ConfigHttpRequests;
 
-// Implement the HTTP bindings for the workers.
-wit_bindgen_wasmtime::export!({paths: ["../../wit/core/http.wit"], async: []});
+// Implement the HTTP bindings for the workers. Requests run in the
+// async runtime of the server, so they don't block its threads.
+wit_bindgen_wasmtime::export!({paths: ["../../wit/core/http.wit"], async: *});
 use http::{Http, HttpError, HttpMethod, HttpRequest, HttpRequestError, HttpResponse};
 
 pub use http::add_to_linker;
 
+/// Headers that are removed when a redirect points to a different host
+const SENSITIVE_HEADERS: [reqwest::header::HeaderName; 3] =
+    [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION];
+
+lazy_static! {
+    /// HTTP clients shared by all the workers. A client keeps a pool of
+    /// connections, so requests to the same host reuse them. The resolver
+    /// of a client checks the addresses, so there's one client for every
+    /// set of address rules and network settings.
+    static ref CLIENTS: Mutex<HashMap<(AddressPolicy, ConfigHttpRequests), reqwest::Client>> =
+        Mutex::new(HashMap::new());
+}
+
 pub struct HttpBindings {
     pub http_config: HttpRequestsConfig,
+    /// Send the requests to the network or use the fixtures
+    mode: HttpMode,
+    /// Number of requests the worker sent in the current run
+    requests: u32,
+}
+
+impl HttpBindings {
+    /// Initialize the bindings for a new run of the worker
+    pub fn new(http_config: HttpRequestsConfig, mode: HttpMode) -> Self {
+        Self {
+            http_config,
+            mode,
+            requests: 0,
+        }
+    }
 }
 
 /// Implement the conversion between HttpMethod and
@@ -52,46 +94,501 @@ impl From<reqwest::Error> for HttpError {
     }
 }
 
+impl From<reqwest::Error> for HttpRequestError {
+    fn from(value: reqwest::Error) -> Self {
+        let message = value.to_string();
+
+        // Manage the different possible errors from Reqwest
+        HttpRequestError {
+            error: value.into(),
+            message,
+        }
+    }
+}
+
+/// Check the worker can send a request to the given URL. The URL
+/// must include a host, so every request goes through the rules.
+fn check_destination(
+    http_config: &HttpRequestsConfig,
+    scheme: Option<&str>,
+    host: Option<&str>,
+    port: Option<u16>,
+) -> Result<(), String> {
+    let default_port =
+        match scheme {
+            Some("https") => 443,
+            Some("http") if http_config.allow_http => 80,
+            Some("http") => return Err(
+                "The URI must use HTTPS. You can allow http requests in the worker configuration"
+                    .to_string(),
+            ),
+            _ => return Err("The URI must use the HTTPS or HTTP schemes".to_string()),
+        };
+
+    let Some(host) = host.filter(|host| !host.is_empty()) else {
+        return Err("The URI must include a host".to_string());
+    };
+
+    if !http_config.is_host_allowed(host, port.unwrap_or(default_port)) {
+        return Err(format!(
+            "The host '{host}' is not allowed for this worker. Please, update the worker configuration."
+        ));
+    }
+
+    // Hosts that are IP addresses don't go through the resolver
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+        if !http_config.is_ip_allowed(ip) {
+            return Err(format!("The address '{ip}' is blocked for this worker."));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the addresses of the host before sending a request through the
+/// proxy. The proxy resolves the hosts of the requests it receives, so the
+/// client resolver only checks the proxy address. It also checks the
+/// requests to the proxy host itself, as the client resolver trusts it.
+async fn check_proxied_host(
+    http_config: &HttpRequestsConfig,
+    url: &Url,
+) -> Result<(), HttpRequestError> {
+    if http_config.network.proxy.is_none() || !http_config.address_policy().blocks_addresses() {
+        return Ok(());
+    }
+
+    let Some(host) = url.host_str() else {
+        return Ok(());
+    };
+
+    // IP addresses were already checked with the rest of the destination
+    if host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    let not_allowed = |message| HttpRequestError {
+        error: HttpError::NotAllowed,
+        message,
+    };
+    let addrs: Vec<SocketAddr> = lookup_host((host, 0))
+        .await
+        .map_err(|error| {
+            not_allowed(format!(
+                "The host '{host}' couldn't be resolved to check its addresses: {error}"
+            ))
+        })?
+        .collect();
+
+    match addrs
+        .iter()
+        .find(|addr| !http_config.is_ip_allowed(addr.ip()))
+    {
+        Some(addr) => Err(not_allowed(format!(
+            "The host '{host}' resolves to the address '{}' that is blocked for this worker.",
+            addr.ip()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Resolve the hosts with the system resolver and discard the addresses
+/// the worker can't reach. Checking the resolved addresses prevents an
+/// allowed domain from pointing to an internal service.
+struct CheckedResolver {
+    policy: AddressPolicy,
+    /// The proxy comes from the configuration, so it may be an internal
+    /// service. Its addresses are not checked here, so the requests that
+    /// point to the proxy host go through `check_proxied_host` first
+    proxy_host: Option<String>,
+}
+
+impl Resolve for CheckedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        let trusted = self.proxy_host.as_deref() == Some(name.as_str());
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| trusted || policy.is_ip_allowed(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!(
+                    "The host '{}' resolves to addresses that are blocked for this worker.",
+                    name.as_str()
+                )
+                .into());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Error for the network settings that can't be applied to the client
+fn network_error(message: String) -> HttpRequestError {
+    HttpRequestError {
+        error: HttpError::InternalError,
+        message,
+    }
+}
+
+/// Read a file from the network settings
+fn read_network_file(path: &Path) -> Result<Vec<u8>, HttpRequestError> {
+    fs::read(path).map_err(|error| {
+        network_error(format!(
+            "There was an error reading the file '{}': {error}",
+            path.display()
+        ))
+    })
+}
+
+/// Split a PEM bundle in its certificates. The TLS backend only loads
+/// the first certificate of a PEM file.
+fn pem_certificates(bundle: &[u8]) -> Vec<Vec<u8>> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    String::from_utf8_lossy(bundle)
+        .split_inclusive(END)
+        .filter_map(|block| {
+            block
+                .find("-----BEGIN CERTIFICATE-----")
+                .map(|start| block[start..].as_bytes().to_vec())
+        })
+        .filter(|block| block.ends_with(END.as_bytes()))
+        .collect()
+}
+
+/// Build a client with the given address rules and network settings
+fn build_client(
+    policy: &AddressPolicy,
+    network: &ConfigHttpRequests,
+) -> Result<reqwest::Client, HttpRequestError> {
+    let proxy = network
+        .proxy
+        .as_deref()
+        .map(|url| {
+            Proxy::all(url)
+                .map(|proxy| {
+                    proxy.no_proxy(
+                        network
+                            .no_proxy
+                            .as_ref()
+                            .and_then(|hosts| NoProxy::from_string(&hosts.join(","))),
+                    )
+                })
+                .map_err(|error| network_error(format!("The proxy '{url}' is not valid: {error}")))
+        })
+        .transpose()?;
+    let proxy_host = network
+        .proxy
+        .as_deref()
+        .and_then(|proxy| Url::parse(proxy).ok())
+        .and_then(|proxy| proxy.host_str().map(String::from));
+
+    let mut builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(CheckedResolver {
+            policy: policy.clone(),
+            proxy_host,
+        }))
+        // The bindings follow the redirects, as they depend on the worker rules
+        .redirect(Policy::none())
+        // Only the proxy in the configuration applies. The ones in the
+        // environment would skip the address checks
+        .no_proxy();
+
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy);
+    }
+
+    for path in network.ca_certificates.iter().flatten() {
+        let certificates = pem_certificates(&read_network_file(path)?);
+
+        if certificates.is_empty() {
+            return Err(network_error(format!(
+                "The file '{}' doesn't contain any PEM certificate",
+                path.display()
+            )));
+        }
+
+        for certificate in certificates {
+            let certificate = Certificate::from_pem(&certificate).map_err(|error| {
+                network_error(format!(
+                    "The certificate in '{}' is not valid: {error}",
+                    path.display()
+                ))
+            })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let Some(client_certificate) = &network.client_certificate {
+        let identity = Identity::from_pkcs8_pem(
+            &read_network_file(&client_certificate.certificate)?,
+            &read_network_file(&client_certificate.key)?,
+        )
+        .map_err(|error| {
+            network_error(format!(
+                "The client certificate '{}' is not valid: {error}",
+                client_certificate.certificate.display()
+            ))
+        })?;
+        builder = builder.identity(identity);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Returns the shared client for the given address rules and network
+/// settings. It's created the first time a worker uses them.
+fn shared_client(
+    policy: AddressPolicy,
+    network: ConfigHttpRequests,
+) -> Result<reqwest::Client, HttpRequestError> {
+    let mut clients = CLIENTS.lock().expect("error locking the HTTP clients");
+    let key = (policy, network);
+
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    let client = build_client(&key.0, &key.1)?;
+    clients.insert(key, client.clone());
+
+    Ok(client)
+}
+
+/// Returns the method and body of the request that follows a redirect.
+/// Like browsers, a 303 status code and the POST requests redirected
+/// with a 301 or 302 switch to a GET request without body.
+fn redirect_request(status: StatusCode, method: Method, body: Vec<u8>) -> (Method, Vec<u8>) {
+    match status {
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => (method, body),
+        StatusCode::SEE_OTHER if method != Method::HEAD => (Method::GET, Vec::new()),
+        _ if method == Method::POST => (Method::GET, Vec::new()),
+        _ => (method, body),
+    }
+}
+
+/// Remove the credentials from the headers when a redirect points to a
+/// different host or port, so they don't leak to other servers
+fn remove_sensitive_headers(headers: &mut Vec<(String, String)>, url: &Url, next_url: &Url) {
+    if next_url.host_str() != url.host_str()
+        || next_url.port_or_known_default() != url.port_or_known_default()
+    {
+        headers.retain(|(key, _)| {
+            !SENSITIVE_HEADERS
+                .iter()
+                .any(|header| header.as_str().eq_ignore_ascii_case(key))
+        });
+    }
+}
+
+/// Send the request and follow the redirects. Every redirect must
+/// follow the worker rules.
+async fn send_with_redirects(
+    client: &reqwest::Client,
+    http_config: &HttpRequestsConfig,
+    mut method: Method,
+    mut url: Url,
+    mut headers: Vec<(String, String)>,
+    mut body: Vec<u8>,
+) -> Result<reqwest::Response, HttpRequestError> {
+    let mut redirects = 0;
+
+    loop {
+        check_proxied_host(http_config, &url).await?;
+
+        let mut builder = client.request(method.clone(), url.clone());
+
+        for (key, value) in headers.iter() {
+            builder = builder.header(key, value);
+        }
+
+        let res = builder.body(body.clone()).send().await?;
+
+        let status = res.status();
+        let location = match status {
+            StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT => res
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok()),
+            _ => None,
+        };
+
+        let Some(next_url) = location else {
+            return Ok(res);
+        };
+
+        if redirects >= http_config.max_redirects {
+            return Err(HttpRequestError {
+                error: HttpError::TooManyRedirects,
+                message: format!(
+                    "The request exceeded the limit of {} redirects",
+                    http_config.max_redirects
+                ),
+            });
+        }
+        redirects += 1;
+
+        check_destination(
+            http_config,
+            Some(next_url.scheme()),
+            next_url.host_str(),
+            next_url.port(),
+        )
+        .map_err(|message| HttpRequestError {
+            error: HttpError::NotAllowed,
+            message,
+        })?;
+
+        remove_sensitive_headers(&mut headers, &url, &next_url);
+        (method, body) = redirect_request(status, method, body);
+        url = next_url;
+    }
+}
+
+/// Read the body of a response. It fails as soon as the body exceeds the
+/// given limit, so the worker doesn't load it completely in memory.
+async fn read_body(
+    res: &mut reqwest::Response,
+    max_response_bytes: Option<usize>,
+) -> Result<Vec<u8>, HttpRequestError> {
+    let too_large = |limit: usize| HttpRequestError {
+        error: HttpError::ResponseTooLarge,
+        message: format!("The response body exceeded the limit of {limit} bytes"),
+    };
+
+    if let (Some(limit), Some(length)) = (max_response_bytes, res.content_length()) {
+        if length > limit as u64 {
+            return Err(too_large(limit));
+        }
+    }
+
+    let mut body = Vec::new();
+
+    while let Some(chunk) = res.chunk().await? {
+        body.extend_from_slice(&chunk);
+
+        if let Some(limit) = max_response_bytes {
+            if body.len() > limit {
+                return Err(too_large(limit));
+            }
+        }
+    }
+
+    Ok(body)
+}
+
+/// Reply with the response saved in the fixtures folder. The fixture
+/// must follow the same limits as the responses from the network.
+fn replay(
+    folder: &Path,
+    request: &RecordedRequest,
+    max_response_bytes: Option<usize>,
+) -> Result<HttpResponse, HttpRequestError> {
+    let response = load_fixture(folder, request).map_err(|error| HttpRequestError {
+        error: HttpError::InternalError,
+        message: error.to_string(),
+    })?;
+
+    if let Some(limit) = max_response_bytes {
+        if response.body.len() > limit {
+            return Err(HttpRequestError {
+                error: HttpError::ResponseTooLarge,
+                message: format!("The response body exceeded the limit of {limit} bytes"),
+            });
+        }
+    }
+
+    Ok(HttpResponse {
+        headers: response.headers,
+        status: response.status,
+        body: Some(response.body),
+    })
+}
+
+/// Send the request and read the response
+async fn fetch(
+    client: &reqwest::Client,
+    http_config: &HttpRequestsConfig,
+    method: Method,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+) -> Result<HttpResponse, HttpRequestError> {
+    let mut res = send_with_redirects(client, http_config, method, url, headers, body).await?;
+
+    let status = res.status().as_u16();
+    let headers = res
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect();
+
+    let body = read_body(&mut res, http_config.max_response_bytes).await?;
+
+    Ok(HttpResponse {
+        headers,
+        status,
+        body: Some(body),
+    })
+}
+
+#[wit_bindgen_wasmtime::async_trait]
 impl Http for HttpBindings {
-    fn send_http_request(
+    async fn send_http_request(
         &mut self,
         req: HttpRequest<'_>,
     ) -> Result<HttpResponse, HttpRequestError> {
+        if let Some(max_requests) = self.http_config.max_requests_per_invocation {
+            if self.requests >= max_requests {
+                return Err(HttpRequestError {
+                    error: HttpError::TooManyRequests,
+                    message: format!(
+                        "The worker exceeded the limit of {max_requests} HTTP requests. Please, update the worker configuration."
+                    ),
+                });
+            }
+        }
+        self.requests += 1;
+
         // Create local variables from the request
-        let mut headers = Vec::new();
-        let url = req.uri.to_string();
         let body = req.body.unwrap_or(&[]).to_vec();
-        let uri = url.parse::<Uri>().map_err(|e| HttpRequestError {
+        let url = Url::parse(req.uri).map_err(|e| HttpRequestError {
             error: HttpError::InvalidRequest,
             message: e.to_string(),
         })?;
         let method: Method = req.method.into();
 
         // Check if the request is allowed
-        if uri.host().is_some()
-            && !self
-                .http_config
-                .allowed_hosts
-                .contains(&uri.host().unwrap().to_string())
-        {
-            return Err(HttpRequestError {
-                error: HttpError::NotAllowed,
-                message: format!(
-                    "The host '{}' is not allowed for this worker. Please, update the worker configuration.",
-                    uri.host().unwrap()
-                ),
-            });
-        }
-
-        if uri.scheme().is_some()
-            && (!self.http_config.allow_http && uri.scheme_str().unwrap() == "http")
-        {
-            return Err(HttpRequestError {
-                error: HttpError::NotAllowed,
-                message:
-                    "The URI must use HTTPS. You can allow http requests in the worker configuration".to_string()
-            });
-        }
+        check_destination(
+            &self.http_config,
+            Some(url.scheme()),
+            url.host_str(),
+            url.port(),
+        )
+        .map_err(|message| HttpRequestError {
+            error: HttpError::NotAllowed,
+            message,
+        })?;
 
         if !self
             .http_config
@@ -105,68 +602,209 @@ impl Http for HttpBindings {
             });
         }
 
-        for (key, value) in req.headers {
-            headers.push((key.to_string(), value.to_string()));
+        let url_str = url.to_string();
+        let recorded = RecordedRequest {
+            method: method.as_str(),
+            url: &url_str,
+            body: &body,
+        };
+
+        if let HttpMode::Replay(folder) = &self.mode {
+            return replay(folder, &recorded, self.http_config.max_response_bytes);
         }
 
-        // Run the request in an async thread
-        let thread_result = std::thread::spawn(move || {
-            Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async {
-                    let client = reqwest::Client::new();
+        let headers = req
+            .headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
 
-                    let mut builder = client.request(method, url);
+        let client = shared_client(
+            self.http_config.address_policy(),
+            self.http_config.network.clone(),
+        )?;
+        let request = fetch(
+            &client,
+            &self.http_config,
+            method.clone(),
+            url,
+            headers,
+            body.clone(),
+        );
 
-                    for (key, value) in headers {
-                        builder = builder.header(key, value);
-                    }
-
-                    builder = builder.body(body);
-
-                    match builder.send().await {
-                        Ok(res) => {
-                            let mut headers = Vec::new();
-                            let status = res.status().as_u16();
-
-                            for (name, value) in res.headers().iter() {
-                                headers
-                                    .push((name.to_string(), value.to_str().unwrap().to_string()));
-                            }
-
-                            let body = res.bytes().await;
-
-                            Ok(HttpResponse {
-                                headers,
-                                status,
-                                body: Some(body.unwrap().to_vec()),
-                            })
-                        }
-                        Err(e) => {
-                            let message = e.to_string();
-
-                            // Manage the different possible errors from Reqwest
-                            Err(HttpRequestError {
-                                error: e.into(),
-                                message,
-                            })
-                        }
-                    }
+        let timeout_ms = self.http_config.timeout_ms;
+        let response = timeout(Duration::from_millis(timeout_ms), request)
+            .await
+            .unwrap_or_else(|_| {
+                Err(HttpRequestError {
+                    error: HttpError::Timeout,
+                    message: format!("The request exceeded the timeout of {timeout_ms}ms"),
                 })
-        })
-        .join();
-
-        match thread_result {
-            Ok(res) => match res {
-                Ok(res) => Ok(res),
-                Err(err) => Err(err),
-            },
-            Err(_) => Err(HttpRequestError {
-                error: HttpError::InternalError,
-                message: "There was an error processing the request on the host side.".to_string(),
-            }),
+            })?;
+
+        // Failed requests are not recorded, so replaying them fails too
+        if let HttpMode::Record(folder) = &self.mode {
+            let recorded_response = RecordedResponse {
+                status: response.status,
+                headers: response.headers.clone(),
+                body: response.body.clone().unwrap_or_default(),
+            };
+
+            if let Err(err) = save_fixture(folder, &recorded, &recorded_response) {
+                eprintln!("{err}");
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn redirects_switch_to_get_like_browsers() {
+        let body = b"data".to_vec();
+        let tests = [
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                Method::POST,
+                Method::GET,
+                false,
+            ),
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                Method::PUT,
+                Method::PUT,
+                true,
+            ),
+            (StatusCode::FOUND, Method::POST, Method::GET, false),
+            (StatusCode::FOUND, Method::DELETE, Method::DELETE, true),
+            (StatusCode::SEE_OTHER, Method::PUT, Method::GET, false),
+            (StatusCode::SEE_OTHER, Method::HEAD, Method::HEAD, true),
+            (
+                StatusCode::TEMPORARY_REDIRECT,
+                Method::POST,
+                Method::POST,
+                true,
+            ),
+            (
+                StatusCode::PERMANENT_REDIRECT,
+                Method::POST,
+                Method::POST,
+                true,
+            ),
+        ];
+
+        for (status, method, expected_method, keeps_body) in tests {
+            let (method, next_body) = redirect_request(status, method, body.clone());
+
+            assert_eq!(method, expected_method, "{status}");
+            assert_eq!(next_body.is_empty(), !keeps_body, "{status}");
         }
     }
+
+    #[test]
+    fn redirects_to_other_servers_remove_the_credentials() {
+        let url = Url::parse("https://api.example.com/login").unwrap();
+        let tests = [
+            ("https://api.example.com/home", true),
+            ("https://api.example.com:443/home", true),
+            ("https://other.example.com/home", false),
+            ("https://api.example.com:8443/home", false),
+            ("http://api.example.com/home", false),
+        ];
+
+        for (next_url, keeps_credentials) in tests {
+            let mut headers = vec![
+                ("Authorization".to_string(), "Bearer token".to_string()),
+                ("cookie".to_string(), "session=1".to_string()),
+                ("Proxy-Authorization".to_string(), "Basic abc".to_string()),
+                ("accept".to_string(), "text/html".to_string()),
+            ];
+
+            remove_sensitive_headers(&mut headers, &url, &Url::parse(next_url).unwrap());
+
+            assert_eq!(
+                headers.len(),
+                if keeps_credentials { 4 } else { 1 },
+                "{next_url}"
+            );
+            assert_eq!(headers.last().unwrap().0, "accept");
+        }
+    }
+
+    #[test]
+    fn read_chunked_bodies_up_to_the_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        // The body doesn't set a length, so the limit applies while reading it
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                          4\r\naaaa\r\n4\r\nbbbb\r\n4\r\ncccc\r\n0\r\n\r\n",
+                    )
+                    .unwrap();
+            }
+        });
+
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let read = |limit| {
+            let client = client.clone();
+            let url = url.clone();
+
+            actix_web::rt::System::new().block_on(async move {
+                let mut res = client.get(url).send().await.unwrap();
+                assert_eq!(res.content_length(), None);
+
+                read_body(&mut res, Some(limit)).await
+            })
+        };
+
+        assert!(matches!(
+            read(10),
+            Err(HttpRequestError {
+                error: HttpError::ResponseTooLarge,
+                ..
+            })
+        ));
+        assert_eq!(read(12).ok(), Some(b"aaaabbbbcccc".to_vec()));
+    }
+
+    #[test]
+    fn proxied_requests_check_the_addresses_of_the_host() {
+        let mut http_config = HttpRequestsConfig::default();
+        http_config.network.proxy = Some("http://proxy.example.com:3128".to_string());
+        let check = |http_config: &HttpRequestsConfig, url: &str| {
+            let url = Url::parse(url).unwrap();
+
+            actix_web::rt::System::new()
+                .block_on(async { check_proxied_host(http_config, &url).await.is_ok() })
+        };
+
+        // The proxy would resolve it to a loopback address
+        assert!(!check(&http_config, "https://localhost/"));
+
+        http_config.allow_private_ips = true;
+        assert!(check(&http_config, "https://localhost/"));
+    }
 }
@@ -0,0 +1,70 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+use wws_data_kv::{errors::KVError, KVStore};
+
+// Implement the K/V bindings for the workers.
+wit_bindgen_wasmtime::export!({paths: ["../../wit/core/kv.wit"], async: []});
+use kv::{Kv, KvError};
+
+pub use kv::add_to_linker;
+
+/// Give access to the K/V store of the worker namespace. Workers that
+/// don't configure a store in host mode get empty results and errors
+/// when writing.
+#[derive(Default)]
+pub struct KvBindings {
+    pub store: Option<Arc<KVStore>>,
+}
+
+/// Map the errors from the K/V store. The details are only logged
+/// on the host side
+fn kv_error(err: KVError, context: &str) -> KvError {
+    eprintln!("{context}: {err}");
+
+    match err {
+        KVError::QuotaExceeded { .. } => KvError::QuotaExceeded,
+        _ => KvError::StorageError,
+    }
+}
+
+impl Kv for KvBindings {
+    fn get(&mut self, key: &str) -> Option<String> {
+        self.store.as_ref()?.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), KvError> {
+        let store = self.store.as_ref().ok_or(KvError::NotAvailable)?;
+
+        store
+            .set(key, value)
+            .map_err(|err| kv_error(err, &format!("Error saving the key {key} in the K/V store")))
+    }
+
+    fn set_with_ttl(&mut self, key: &str, value: &str, ttl_secs: u64) -> Result<(), KvError> {
+        let store = self.store.as_ref().ok_or(KvError::NotAvailable)?;
+
+        store
+            .set_with_ttl(key, value, ttl_secs)
+            .map_err(|err| kv_error(err, &format!("Error saving the key {key} in the K/V store")))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), KvError> {
+        let store = self.store.as_ref().ok_or(KvError::NotAvailable)?;
+
+        store.delete(key).map_err(|err| {
+            kv_error(
+                err,
+                &format!("Error deleting the key {key} from the K/V store"),
+            )
+        })
+    }
+
+    fn list_keys(&mut self) -> Vec<String> {
+        self.store
+            .as_ref()
+            .map(|store| store.keys())
+            .unwrap_or_default()
+    }
+}
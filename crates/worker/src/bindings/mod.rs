@@ -2,3 +2,5 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod http;
+pub mod kv;
+pub mod sql;
@@ -0,0 +1,144 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+use wws_data_sql::{errors::SQLError, SQLCancellation, SQLDatabase, SQLValue};
+
+// Implement the SQL bindings for the workers. Statements can wait for the
+// database locks, so they run in a blocking thread instead of the server ones.
+wit_bindgen_wasmtime::export!({paths: ["../../wit/core/sql.wit"], async: *});
+use sql::{Sql, SqlError, SqlRows, SqlStatementError, SqlValueParam, SqlValueResult};
+
+pub use sql::add_to_linker;
+
+/// Give access to the SQL databases listed in the worker configuration.
+#[derive(Default)]
+pub struct SqlBindings {
+    pub databases: HashMap<String, Arc<SQLDatabase>>,
+}
+
+impl SqlBindings {
+    /// Look for one of the databases the worker can access
+    fn database(&self, name: &str) -> Result<Arc<SQLDatabase>, SqlStatementError> {
+        self.databases
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SqlStatementError {
+                error: SqlError::NotAllowed,
+                message: format!(
+                    "The database '{name}' is not allowed for this worker. Please, update the worker configuration."
+                ),
+            })
+    }
+}
+
+/// Interrupts the statement when the worker stops waiting for it, like
+/// when it exceeds its timeout. The blocking thread keeps running otherwise
+struct CancelOnDrop(SQLCancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Run a statement in the blocking threads of the async runtime
+async fn run_blocking<T, F>(database: Arc<SQLDatabase>, run: F) -> Result<T, SqlStatementError>
+where
+    T: Send + 'static,
+    F: FnOnce(&SQLDatabase, &SQLCancellation) -> Result<T, SQLError> + Send + 'static,
+{
+    let cancellation = SQLCancellation::default();
+    let _guard = CancelOnDrop(cancellation.clone());
+
+    match spawn_blocking(move || run(&database, &cancellation)).await {
+        Ok(result) => Ok(result?),
+        Err(err) => Err(SqlStatementError {
+            error: SqlError::StorageError,
+            message: format!("The statement couldn't run: {err}"),
+        }),
+    }
+}
+
+impl From<SqlValueParam<'_>> for SQLValue {
+    fn from(value: SqlValueParam<'_>) -> Self {
+        match value {
+            SqlValueParam::Null => SQLValue::Null,
+            SqlValueParam::Integer(value) => SQLValue::Integer(value),
+            SqlValueParam::Real(value) => SQLValue::Real(value),
+            SqlValueParam::Text(value) => SQLValue::Text(value.to_string()),
+            SqlValueParam::Blob(value) => SQLValue::Blob(value.to_vec()),
+        }
+    }
+}
+
+impl From<SQLValue> for SqlValueResult {
+    fn from(value: SQLValue) -> Self {
+        match value {
+            SQLValue::Null => SqlValueResult::Null,
+            SQLValue::Integer(value) => SqlValueResult::Integer(value),
+            SQLValue::Real(value) => SqlValueResult::Real(value),
+            SQLValue::Text(value) => SqlValueResult::Text(value),
+            SQLValue::Blob(value) => SqlValueResult::Blob(value),
+        }
+    }
+}
+
+/// Map the errors from the SQL databases. The message is sent to the
+/// worker, so developers can fix their statements
+impl From<SQLError> for SqlStatementError {
+    fn from(value: SQLError) -> Self {
+        let error = match value {
+            SQLError::Statement { .. } => SqlError::InvalidStatement,
+            _ => SqlError::StorageError,
+        };
+
+        SqlStatementError {
+            error,
+            message: value.to_string(),
+        }
+    }
+}
+
+#[wit_bindgen_wasmtime::async_trait]
+impl Sql for SqlBindings {
+    async fn query(
+        &mut self,
+        database: &str,
+        statement: &str,
+        params: Vec<SqlValueParam<'_>>,
+    ) -> Result<SqlRows, SqlStatementError> {
+        let statement = statement.to_string();
+        let params: Vec<SQLValue> = params.into_iter().map(SQLValue::from).collect();
+        let result = run_blocking(self.database(database)?, move |database, cancellation| {
+            database.query(&statement, &params, cancellation)
+        })
+        .await?;
+
+        Ok(SqlRows {
+            columns: result.columns,
+            rows: result
+                .rows
+                .into_iter()
+                .map(|row| row.into_iter().map(SqlValueResult::from).collect())
+                .collect(),
+        })
+    }
+
+    async fn execute(
+        &mut self,
+        database: &str,
+        statement: &str,
+        params: Vec<SqlValueParam<'_>>,
+    ) -> Result<u64, SqlStatementError> {
+        let statement = statement.to_string();
+        let params: Vec<SQLValue> = params.into_iter().map(SQLValue::from).collect();
+
+        run_blocking(self.database(database)?, move |database, cancellation| {
+            database.execute(&statement, &params, cancellation)
+        })
+        .await
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::{self, Result};
+use crate::ModuleOrComponent;
+use lazy_static::lazy_static;
+use sha256::digest as sha256_digest;
+use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Mutex;
+use wasmtime::{component::Component, Engine, Module};
+use wws_store::Store;
+
+/// Folder inside the project store that keeps the compiled modules
+/// and components.
+const COMPILED_FOLDER: [&str; 2] = ["cache", "compiled"];
+
+/// Extension for the serialized artifacts
+const COMPILED_EXTENSION: &str = "cwasm";
+
+lazy_static! {
+    /// Modules and components loaded in this process. Workers that run
+    /// the same Wasm binary, like the JavaScript engine or any external
+    /// language runtime, share the same compiled module.
+    static ref LOADED: Mutex<HashMap<String, ModuleOrComponent>> = Mutex::new(HashMap::new());
+}
+
+/// Load the Wasm module or component from the given bytes. Modules that
+/// were already loaded by other workers are reused. Compiling a module
+/// with Cranelift is expensive, so this method looks for a compiled
+/// artifact in the project store before compiling it. If it's not there
+/// or it's not valid anymore, it compiles the module and saves the
+/// result for the next runs.
+pub fn load_module_or_component(
+    project_root: &Path,
+    engine: &Engine,
+    bytes: &[u8],
+    key: &str,
+) -> Result<ModuleOrComponent> {
+    if let Some(module_or_component) = LOADED
+        .lock()
+        .expect("error locking the compiled modules cache")
+        .get(key)
+    {
+        return Ok(module_or_component.clone());
+    }
+
+    // The lock is not held while compiling, so other workers can load
+    // in the meantime. If another worker loaded the same module first,
+    // keep that one.
+    let module_or_component = load_from_store_or_compile(project_root, engine, bytes, key)?;
+
+    Ok(LOADED
+        .lock()
+        .expect("error locking the compiled modules cache")
+        .entry(key.to_string())
+        .or_insert(module_or_component)
+        .clone())
+}
+
+/// Remove the modules and components that none of the given keys use
+/// from the memory and the project store. Reloading a worker compiles
+/// its new module, so the previous one would stay forever otherwise.
+pub fn evict_unused(project_root: &Path, used_keys: &HashSet<&str>) {
+    LOADED
+        .lock()
+        .expect("error locking the compiled modules cache")
+        .retain(|key, _| used_keys.contains(key.as_str()));
+
+    let folder = Store::new(project_root, &COMPILED_FOLDER).build_folder_path(&[]);
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let unused = path
+            .extension()
+            .map_or(false, |ext| ext == COMPILED_EXTENSION)
+            && path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map_or(false, |key| !used_keys.contains(key));
+
+        if unused {
+            if let Err(err) = fs::remove_file(&path) {
+                eprintln!(
+                    "Error removing the compiled module {}: {err}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+/// Load the compiled artifact from the project store or compile the
+/// module if it's not available.
+fn load_from_store_or_compile(
+    project_root: &Path,
+    engine: &Engine,
+    bytes: &[u8],
+    key: &str,
+) -> Result<ModuleOrComponent> {
+    let store = Store::new(project_root, &COMPILED_FOLDER);
+    let filename = format!("{key}.{COMPILED_EXTENSION}");
+
+    if store.check_file(&[&filename]) {
+        if let Some(module_or_component) = store
+            .read(&[&filename])
+            .ok()
+            .and_then(|compiled| deserialize(engine, bytes, &compiled))
+        {
+            return Ok(module_or_component);
+        }
+    }
+
+    let module_or_component = compile(engine, bytes)?;
+
+    // The cache is an optimization. A failure here should not stop
+    // the worker from running.
+    if let Err(err) = save(project_root, &filename, &module_or_component) {
+        eprintln!("Error saving the compiled module to the project store: {err}");
+    }
+
+    Ok(module_or_component)
+}
+
+/// Compile the given bytes. They can be a Wasm core module or a component.
+fn compile(engine: &Engine, bytes: &[u8]) -> Result<ModuleOrComponent> {
+    if wasmparser::Parser::is_core_wasm(bytes) {
+        Ok(ModuleOrComponent::Module(
+            Module::from_binary(engine, bytes).map_err(|err| {
+                errors::WorkerError::BadWasmCoreModule {
+                    error: format!("{:?}", err),
+                }
+            })?,
+        ))
+    } else if wasmparser::Parser::is_component(bytes) {
+        Ok(ModuleOrComponent::Component(
+            Component::from_binary(engine, bytes).map_err(|err| {
+                errors::WorkerError::BadWasmComponent {
+                    error: format!("{:?}", err),
+                }
+            })?,
+        ))
+    } else {
+        Err(errors::WorkerError::BadWasmCoreModuleOrComponent)
+    }
+}
+
+/// Load a compiled artifact. It returns `None` when wasmtime rejects it,
+/// so the caller can compile the module again.
+fn deserialize(engine: &Engine, bytes: &[u8], compiled: &[u8]) -> Option<ModuleOrComponent> {
+    // SAFETY: the compiled artifacts are created by wws in the project
+    // store. Wasmtime validates the artifact was generated by the same
+    // version and configuration before loading it.
+    let result = if wasmparser::Parser::is_core_wasm(bytes) {
+        unsafe { Module::deserialize(engine, compiled) }.map(ModuleOrComponent::Module)
+    } else {
+        unsafe { Component::deserialize(engine, compiled) }.map(ModuleOrComponent::Component)
+    };
+
+    result.ok()
+}
+
+/// Serialize the module or component and write it into the project store
+fn save(
+    project_root: &Path,
+    filename: &str,
+    module_or_component: &ModuleOrComponent,
+) -> Result<()> {
+    let compiled = match module_or_component {
+        ModuleOrComponent::Module(module) => module.serialize(),
+        ModuleOrComponent::Component(component) => component.serialize(),
+    }
+    .map_err(|err| errors::WorkerError::CannotSaveCompiledModule {
+        error: format!("{err}"),
+    })?;
+
+    let store = Store::create(project_root, &COMPILED_FOLDER).map_err(|err| {
+        errors::WorkerError::CannotSaveCompiledModule {
+            error: format!("{err}"),
+        }
+    })?;
+
+    store.write(&[filename], &compiled).map_err(|err| {
+        errors::WorkerError::CannotSaveCompiledModule {
+            error: format!("{err}"),
+        }
+    })
+}
+
+/// Build the identifier of a compiled artifact. It combines the hash of
+/// the module bytes and the engine settings, so a change in the wasmtime
+/// version or configuration generates a different key.
+pub fn cache_key(engine: &Engine, bytes: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+
+    format!("{}-{:016x}", sha256_digest(bytes), hasher.finish())
+}
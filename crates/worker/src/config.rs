@@ -5,12 +5,14 @@ use crate::errors::Result;
 use crate::features::http_requests::HttpRequestsConfig;
 use crate::features::wasi_nn::WasiNnConfig;
 use crate::features::{data::ConfigData, folders::Folder};
+use crate::limits::LimitsConfig;
 use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::{env, fs};
 use toml::from_str;
 use wws_data_kv::KVConfigData;
+use wws_data_sql::SQLConfigData;
 
 /// List all available features for a worker
 #[derive(Deserialize, Clone, Default)]
@@ -36,6 +38,9 @@ pub struct Config {
     pub data: Option<ConfigData>,
     /// Optional folders
     pub folders: Option<Vec<Folder>>,
+    /// Execution limits. By default, workers run without limits
+    #[serde(default)]
+    pub limits: LimitsConfig,
     /// Optional environment configuration
     #[serde(deserialize_with = "read_environment_variables", default)]
     pub vars: HashMap<String, String>,
@@ -72,6 +77,11 @@ impl Config {
     pub fn data_kv_namespace(&self) -> Option<String> {
         Some(self.data_kv_config()?.namespace.clone())
     }
+
+    /// Returns a data SQL configuration if available
+    pub fn data_sql_config(&self) -> Option<&SQLConfigData> {
+        self.data.as_ref()?.sql.as_ref()
+    }
 }
 
 /// Deserialize the HashMap of variables. By default, this
@@ -0,0 +1,78 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::errors::{self, Result};
+use lazy_static::lazy_static;
+use std::thread;
+use std::time::Duration;
+use wasmtime::{Config as WasmtimeConfig, Engine};
+
+/// Interval between two epoch increments. It's the precision of
+/// the worker timeouts.
+const EPOCH_TICK_MS: u64 = 10;
+
+/// Epoch deadline for the workers that don't set a timeout. The engine
+/// will never reach it. Wasmtime adds the deadline to the current epoch,
+/// so it can't be `u64::MAX`.
+const NO_DEADLINE: u64 = u64::MAX / 2;
+
+lazy_static! {
+    /// Wasmtime engine shared by all the workers. Compiled modules are
+    /// tied to the engine that created them, so sharing it allows workers
+    /// to reuse the same compiled module.
+    static ref ENGINE: std::result::Result<Engine, String> = build_engine(false);
+
+    /// Engine for the workers that set a fuel limit. Fuel metering slows
+    /// down the execution, so only those workers pay for it.
+    static ref FUEL_ENGINE: std::result::Result<Engine, String> = build_engine(true);
+}
+
+/// Create a new engine and start increasing its epoch in the background.
+/// Workers are interrupted when the epoch reaches their deadline.
+fn build_engine(consume_fuel: bool) -> std::result::Result<Engine, String> {
+    let engine = Engine::new(
+        WasmtimeConfig::default()
+            .async_support(true)
+            .wasm_component_model(true)
+            .epoch_interruption(true)
+            .consume_fuel(consume_fuel),
+    )
+    .map_err(|err| err.to_string())?;
+
+    let ticker = engine.clone();
+    thread::Builder::new()
+        .name("wws-epoch".to_string())
+        .spawn(move || loop {
+            thread::sleep(Duration::from_millis(EPOCH_TICK_MS));
+            ticker.increment_epoch();
+        })
+        .map_err(|err| err.to_string())?;
+
+    Ok(engine)
+}
+
+/// Returns the process-wide engine. Engines are cheap to clone, as all
+/// the clones point to the same underlying engine. Workers with a fuel
+/// limit get an engine with fuel metering enabled.
+pub fn shared_engine(consume_fuel: bool) -> Result<Engine> {
+    let engine = if consume_fuel {
+        &*FUEL_ENGINE
+    } else {
+        &*ENGINE
+    };
+
+    engine
+        .clone()
+        .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
+            error: format!("error creating engine ({error})"),
+        })
+}
+
+/// Number of epoch ticks before interrupting a worker with the given
+/// timeout.
+pub fn epoch_deadline(timeout_ms: Option<u64>) -> u64 {
+    match timeout_ms {
+        Some(timeout_ms) => timeout_ms.div_ceil(EPOCH_TICK_MS).max(1),
+        None => NO_DEADLINE,
+    }
+}
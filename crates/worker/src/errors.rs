@@ -19,12 +19,27 @@ pub enum WorkerError {
         path: PathBuf,
         error: toml::de::Error,
     },
+    CannotSaveCompiledModule {
+        error: String,
+    },
     ConfigureRuntimeError {
         error: String,
     },
     DeserializeConfigError,
     FailedToInitialize,
+    OutOfFuel {
+        fuel: u64,
+    },
+    ResourceLimitExceeded {
+        limit: String,
+    },
+    ResponseTooLarge {
+        limit: usize,
+    },
     RuntimeError(wws_runtimes::errors::RuntimeError),
+    Timeout {
+        timeout_ms: u64,
+    },
     WorkerBodyReadError,
 }
 
@@ -56,14 +71,30 @@ impl std::fmt::Display for WorkerError {
                 "Could not parse configuration at {:?}: {:?}",
                 path, error
             ),
+            WorkerError::CannotSaveCompiledModule { error } => {
+                write!(f, "Could not save the compiled module: {error}")
+            }
             WorkerError::ConfigureRuntimeError { error } => {
                 write!(f, "Error configuring runtime: {error}")
             }
             WorkerError::DeserializeConfigError => write!(f, "Error deserializing configuration"),
             WorkerError::FailedToInitialize => write!(f, "Failed to initialize"),
+            WorkerError::OutOfFuel { fuel } => {
+                write!(f, "The worker consumed all its fuel ({fuel})")
+            }
+            WorkerError::ResourceLimitExceeded { limit } => {
+                write!(f, "The worker exceeded its resource limits: {limit}")
+            }
+            WorkerError::ResponseTooLarge { limit } => write!(
+                f,
+                "The worker response is too large. The limit is {limit} bytes"
+            ),
             WorkerError::RuntimeError(error) => {
                 write!(f, "Error on Wasm module runtime: {:?}", error)
             }
+            WorkerError::Timeout { timeout_ms } => {
+                write!(f, "The worker exceeded its timeout ({timeout_ms}ms)")
+            }
             WorkerError::WorkerBodyReadError => write!(f, "Error reading body from worker"),
         }
     }
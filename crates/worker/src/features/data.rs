@@ -3,10 +3,13 @@
 
 use serde::Deserialize;
 use wws_data_kv::KVConfigData;
+use wws_data_sql::SQLConfigData;
 
 /// Configure a data plugin for the worker
 #[derive(Deserialize, Clone, Default)]
 pub struct ConfigData {
     /// Creates a Key/Value store associated to the given worker
     pub kv: Option<KVConfigData>,
+    /// Gives access to the given SQL databases
+    pub sql: Option<SQLConfigData>,
 }
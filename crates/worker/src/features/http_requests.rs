@@ -1,17 +1,84 @@
 // Copyright 2023 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use lazy_static::lazy_static;
 use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use wws_config::ConfigHttpRequests;
+
+/// Number of redirects a request follows by default. It's the same as
+/// the reqwest default policy
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Time in milliseconds a request can take by default. A worker waits for
+/// its requests, so a server that never replies can't block it forever
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+lazy_static! {
+    /// Address ranges workers can't reach by default. They include the
+    /// private, loopback, link-local, reserved and multicast addresses, so
+    /// a worker can't use the server to access internal services (SSRF).
+    /// NAT64 addresses are included too, as they can embed any IPv4 address
+    static ref PRIVATE_RANGES: Vec<IpRange> = [
+        "0.0.0.0/8",
+        "10.0.0.0/8",
+        "100.64.0.0/10",
+        "127.0.0.0/8",
+        "169.254.0.0/16",
+        "172.16.0.0/12",
+        "192.0.0.0/24",
+        "192.168.0.0/16",
+        "198.18.0.0/15",
+        "224.0.0.0/4",
+        "240.0.0.0/4",
+        "::/128",
+        "::1/128",
+        "64:ff9b::/96",
+        "fc00::/7",
+        "fe80::/10",
+        "ff00::/8",
+    ]
+    .iter()
+    .map(|range| range.parse().expect("error parsing the private IP ranges"))
+    .collect();
+}
 
 #[derive(Deserialize, Clone)]
 #[serde(default)]
 pub struct HttpRequestsConfig {
-    /// List of allowed domains to perform the calls
-    pub allowed_hosts: Vec<String>,
+    /// List of allowed hosts to perform the calls. A rule can be a domain,
+    /// a wildcard domain like `*.example.com`, an IP address or a CIDR range
+    /// like `10.0.0.0/8`. Append a port to restrict the rule to it, like
+    /// `example.com:8080` or `[2001:db8::1]:8080`
+    pub allowed_hosts: Vec<HostRule>,
     /// List of allowed HTTP methods for the worker
     pub allowed_methods: Vec<String>,
     /// Allow HTTP requests
     pub allow_http: bool,
+    /// Allow requests to private, loopback and link-local addresses. IP
+    /// rules in `allowed_hosts` allow their own ranges too
+    pub allow_private_ips: bool,
+    /// List of IP addresses or CIDR ranges the worker can never reach,
+    /// even if a rule allows them
+    pub denied_ips: Vec<IpRange>,
+    /// Maximum time in milliseconds to complete a request, including
+    /// the response body. It's 30 seconds by default
+    pub timeout_ms: u64,
+    /// Maximum number of redirects to follow in a request. It follows
+    /// up to 10 redirects by default
+    pub max_redirects: usize,
+    /// Maximum size in bytes of a response body
+    pub max_response_bytes: Option<usize>,
+    /// Maximum number of requests the worker can send while it processes
+    /// a single request
+    pub max_requests_per_invocation: Option<u32>,
+    /// Proxy, certificate authorities and client certificate for the
+    /// requests. The project settings apply to the ones the worker
+    /// doesn't set
+    #[serde(flatten)]
+    pub network: ConfigHttpRequests,
 }
 
 impl Default for HttpRequestsConfig {
@@ -26,6 +93,418 @@ impl Default for HttpRequestsConfig {
                 String::from("DELETE"),
             ]),
             allow_http: false,
+            allow_private_ips: false,
+            denied_ips: Vec::default(),
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            max_response_bytes: None,
+            max_requests_per_invocation: None,
+            network: ConfigHttpRequests::default(),
+        }
+    }
+}
+
+impl HttpRequestsConfig {
+    /// Check if the worker can send requests to the given host and port.
+    /// The host may be a domain or an IP address.
+    pub fn is_host_allowed(&self, host: &str, port: u16) -> bool {
+        let host = normalize_host(host);
+
+        self.allowed_hosts
+            .iter()
+            .any(|rule| rule.matches(&host, port))
+    }
+
+    /// Check if the worker can connect to the given address. It's
+    /// checked after resolving the host, so an allowed domain can't
+    /// point to an internal service.
+    pub fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+        self.address_policy().is_ip_allowed(ip)
+    }
+
+    /// Returns the rules that apply to the addresses the worker connects to
+    pub fn address_policy(&self) -> AddressPolicy {
+        AddressPolicy {
+            allow_private_ips: self.allow_private_ips,
+            allowed_ranges: self
+                .allowed_hosts
+                .iter()
+                .filter_map(|rule| match rule.host {
+                    HostPattern::Ip(range) => Some(range),
+                    _ => None,
+                })
+                .collect(),
+            denied_ranges: self.denied_ips.clone(),
+        }
+    }
+}
+
+/// The rules to check the addresses a worker connects to. Workers with
+/// the same rules can share the same connections.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AddressPolicy {
+    /// Allow all the private, loopback and link-local addresses
+    allow_private_ips: bool,
+    /// Ranges that are explicitly allowed, even if they are private
+    allowed_ranges: Vec<IpRange>,
+    /// Ranges that are always blocked
+    denied_ranges: Vec<IpRange>,
+}
+
+impl AddressPolicy {
+    /// Check if the given address follows the rules
+    pub fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+        let ip = canonical_ip(ip);
+
+        if self.denied_ranges.iter().any(|range| range.contains(ip)) {
+            return false;
         }
+
+        self.allow_private_ips
+            || !PRIVATE_RANGES.iter().any(|range| range.contains(ip))
+            || self.allowed_ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Check if the rules block any address. Otherwise, the worker can
+    /// connect to any of them
+    pub fn blocks_addresses(&self) -> bool {
+        !self.allow_private_ips || !self.denied_ranges.is_empty()
+    }
+}
+
+/// The hosts a rule matches
+#[derive(Clone, Debug, PartialEq)]
+enum HostPattern {
+    /// A single domain
+    Domain(String),
+    /// All the subdomains of a domain. It keeps the leading dot
+    Wildcard(String),
+    /// An IP address or range
+    Ip(IpRange),
+}
+
+/// A rule in the list of allowed hosts. It matches all the ports when
+/// the rule doesn't set one.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub struct HostRule {
+    host: HostPattern,
+    port: Option<u16>,
+}
+
+impl HostRule {
+    /// Check if the rule matches a normalized host and port
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|rule_port| rule_port != port) {
+            return false;
+        }
+
+        match &self.host {
+            HostPattern::Domain(domain) => host == domain,
+            HostPattern::Wildcard(suffix) => host.len() > suffix.len() && host.ends_with(suffix),
+            HostPattern::Ip(range) => host
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| range.contains(canonical_ip(ip))),
+        }
+    }
+}
+
+impl FromStr for HostRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid host rule '{value}'");
+
+        // IPv6 addresses use brackets to separate the port
+        let (host, port) = if let Some(rest) = value.strip_prefix('[') {
+            let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+
+            match rest {
+                "" => (host, None),
+                _ => (host, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+            }
+        } else if value.matches(':').count() > 1 {
+            (value, None)
+        } else {
+            match value.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (value, None),
+            }
+        };
+
+        let port = port
+            .map(|port| port.parse::<u16>().map_err(|_| invalid()))
+            .transpose()?;
+
+        let host = if let Ok(range) = host.parse::<IpRange>() {
+            HostPattern::Ip(range)
+        } else {
+            let domain = normalize_host(host);
+            let (pattern, name) = match domain.strip_prefix("*.") {
+                Some(name) => (HostPattern::Wildcard(format!(".{name}")), name),
+                None => (HostPattern::Domain(domain.clone()), domain.as_str()),
+            };
+
+            if !is_valid_domain(name) {
+                return Err(invalid());
+            }
+
+            pattern
+        };
+
+        Ok(Self { host, port })
+    }
+}
+
+impl TryFrom<String> for HostRule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// An IP address or a CIDR range, like `10.0.0.0/8`
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Check if the range contains the given address. IPv4 ranges
+    /// don't contain IPv6 addresses and vice versa
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip, bits) = match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                (u32::from(network) as u128, u32::from(ip) as u128, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip), 128),
+            _ => return false,
+        };
+
+        let mask = match self.prefix {
+            0 => 0,
+            prefix => (u128::MAX << (bits - prefix)) & (u128::MAX >> (128 - bits)),
+        };
+
+        network & mask == ip & mask
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP range '{value}'");
+        let (network, prefix) = match value.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix)),
+            None => (value, None),
+        };
+
+        let network = network.parse::<IpAddr>().map_err(|_| invalid())?;
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+
+        Ok(Self { network, prefix })
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Lowercase the host and remove the brackets of IPv6 addresses and
+/// the trailing dot of fully qualified domains
+fn normalize_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// IPv4 addresses can be mapped into IPv6 ones. Check them as IPv4,
+/// so they don't skip the IPv4 ranges
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => ip
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(ip)),
+        ip => ip,
+    }
+}
+
+/// Check the domain only contains valid characters
+fn is_valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn config(allowed_hosts: &[&str]) -> HttpRequestsConfig {
+        HttpRequestsConfig {
+            allowed_hosts: allowed_hosts
+                .iter()
+                .map(|rule| rule.parse().unwrap())
+                .collect(),
+            ..HttpRequestsConfig::default()
+        }
+    }
+
+    #[test]
+    fn match_domains_and_wildcards() {
+        let config = config(&["example.com", "*.wasmlabs.dev"]);
+
+        assert!(config.is_host_allowed("example.com", 443));
+        assert!(config.is_host_allowed("EXAMPLE.com.", 80));
+        assert!(!config.is_host_allowed("api.example.com", 443));
+        assert!(config.is_host_allowed("api.wasmlabs.dev", 443));
+        assert!(config.is_host_allowed("a.b.wasmlabs.dev", 443));
+        assert!(!config.is_host_allowed("wasmlabs.dev", 443));
+        assert!(!config.is_host_allowed("evilwasmlabs.dev", 443));
+    }
+
+    #[test]
+    fn match_ports() {
+        let config = config(&["example.com:8080", "[2001:db8::1]:443"]);
+
+        assert!(config.is_host_allowed("example.com", 8080));
+        assert!(!config.is_host_allowed("example.com", 443));
+        assert!(config.is_host_allowed("[2001:db8::1]", 443));
+        assert!(!config.is_host_allowed("[2001:db8::1]", 80));
+    }
+
+    #[test]
+    fn match_ip_ranges() {
+        let config = config(&["10.0.0.0/8", "2001:db8::/32", "203.0.113.7"]);
+
+        assert!(config.is_host_allowed("10.1.2.3", 443));
+        assert!(!config.is_host_allowed("11.1.2.3", 443));
+        assert!(config.is_host_allowed("[2001:db8::42]", 443));
+        assert!(config.is_host_allowed("203.0.113.7", 443));
+        assert!(!config.is_host_allowed("203.0.113.8", 443));
+    }
+
+    #[test]
+    fn reject_invalid_rules() {
+        assert!("exa mple.com".parse::<HostRule>().is_err());
+        assert!("example.com:http".parse::<HostRule>().is_err());
+        assert!("*.".parse::<HostRule>().is_err());
+        assert!("api.*.com".parse::<HostRule>().is_err());
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn block_private_addresses_by_default() {
+        let config = config(&["example.com"]);
+
+        assert!(config.is_ip_allowed("93.184.216.34".parse().unwrap()));
+        assert!(!config.is_ip_allowed("127.0.0.1".parse().unwrap()));
+        assert!(!config.is_ip_allowed("192.168.1.1".parse().unwrap()));
+        assert!(!config.is_ip_allowed("169.254.169.254".parse().unwrap()));
+        assert!(!config.is_ip_allowed("::1".parse().unwrap()));
+        assert!(!config.is_ip_allowed("::ffff:127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn block_reserved_and_multicast_addresses_by_default() {
+        let config = config(&["example.com"]);
+
+        for ip in [
+            "100.64.0.1",
+            "192.0.0.8",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.251",
+            "239.255.255.250",
+            "255.255.255.255",
+            "64:ff9b::a9fe:a9fe",
+            "ff02::1",
+        ] {
+            assert!(!config.is_ip_allowed(ip.parse().unwrap()), "{ip}");
+        }
+
+        assert!(config.is_ip_allowed("198.20.0.1".parse().unwrap()));
+        assert!(config.is_ip_allowed("2606:2800:220:1::".parse().unwrap()));
+    }
+
+    #[test]
+    fn allow_private_addresses_explicitly() {
+        let mut config = config(&["10.0.0.0/24"]);
+
+        assert!(config.is_ip_allowed("10.0.0.5".parse().unwrap()));
+        assert!(!config.is_ip_allowed("10.0.1.5".parse().unwrap()));
+
+        config.allow_private_ips = true;
+        assert!(config.is_ip_allowed("10.0.1.5".parse().unwrap()));
+
+        config.denied_ips = vec!["10.0.0.0/8".parse().unwrap()];
+        assert!(!config.is_ip_allowed("10.0.0.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn merge_network_settings_with_the_project_defaults() {
+        let config: HttpRequestsConfig = toml::from_str(
+            r#"
+            allowed_hosts = ["api.example.com"]
+            proxy = "http://proxy.example.com:3128"
+
+            [client_certificate]
+            certificate = "certs/client.pem"
+            key = "certs/client.key"
+            "#,
+        )
+        .unwrap();
+        let defaults = ConfigHttpRequests {
+            proxy: Some("http://default.example.com:3128".to_string()),
+            ca_certificates: Some(vec![PathBuf::from("/project/ca.pem")]),
+            ..ConfigHttpRequests::default()
+        };
+
+        let network = config
+            .network
+            .with_base_path(Path::new("/project/api"))
+            .or(&defaults);
+
+        assert_eq!(
+            network.proxy.as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(
+            network.ca_certificates,
+            Some(vec![PathBuf::from("/project/ca.pem")])
+        );
+        assert_eq!(
+            network.client_certificate.unwrap().key,
+            PathBuf::from("/project/api/certs/client.key")
+        );
     }
 }
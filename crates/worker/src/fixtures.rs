@@ -0,0 +1,282 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha256::digest as sha256_digest;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Characters of the request hash in the fixture file names
+const HASH_LENGTH: usize = 16;
+
+/// How the workers send their HTTP requests
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum HttpMode {
+    /// Send the requests to the network
+    #[default]
+    Live,
+    /// Send the requests and save every response as a fixture
+    /// in the given folder
+    Record(PathBuf),
+    /// Reply with the fixtures in the given folder. The requests
+    /// never reach the network
+    Replay(PathBuf),
+}
+
+#[derive(Debug)]
+pub enum FixtureError {
+    /// There's no fixture for the request
+    Missing {
+        method: String,
+        url: String,
+        path: PathBuf,
+    },
+    CannotRead {
+        path: PathBuf,
+        error: io::Error,
+    },
+    CannotParse {
+        path: PathBuf,
+        error: String,
+    },
+    CannotWrite {
+        path: PathBuf,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing { method, url, path } => write!(
+                f,
+                "There's no HTTP fixture for the request {method} {url}. Record it with --http-record to create the '{}' file",
+                path.display()
+            ),
+            Self::CannotRead { path, error } => write!(
+                f,
+                "There was an error reading the HTTP fixture '{}': {error}",
+                path.display()
+            ),
+            Self::CannotParse { path, error } => write!(
+                f,
+                "The HTTP fixture '{}' is not valid: {error}",
+                path.display()
+            ),
+            Self::CannotWrite { path, error } => write!(
+                f,
+                "There was an error saving the HTTP fixture '{}': {error}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// The request that identifies a fixture. Request headers are not part
+/// of it, so credentials are never saved to disk.
+pub(crate) struct RecordedRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    pub body: &'a [u8],
+}
+
+impl RecordedRequest<'_> {
+    /// The fixture file of the request. It includes the method and host
+    /// so developers can find it, and a hash of the full request.
+    pub fn fixture_path(&self, folder: &Path) -> PathBuf {
+        let mut data = format!("{} {}\n", self.method, self.url).into_bytes();
+        data.extend_from_slice(self.body);
+        let hash = sha256_digest(data.as_slice());
+
+        let host: String = reqwest::Url::parse(self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        folder.join(format!(
+            "{}-{host}-{}.json",
+            self.method.to_lowercase(),
+            &hash[..HASH_LENGTH]
+        ))
+    }
+}
+
+/// A response saved in a fixture
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// The content of a fixture file. Bodies that are not valid UTF-8
+/// are encoded as base64
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    request: FixtureRequest,
+    response: FixtureResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureRequest {
+    method: String,
+    url: String,
+    body: String,
+    #[serde(default)]
+    base64: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    #[serde(default)]
+    base64: bool,
+}
+
+fn encode_body(body: &[u8]) -> (String, bool) {
+    match std::str::from_utf8(body) {
+        Ok(body) => (body.to_string(), false),
+        Err(_) => (general_purpose::STANDARD.encode(body), true),
+    }
+}
+
+/// Save the response of the request in the given folder. It returns
+/// the path of the fixture.
+pub(crate) fn save_fixture(
+    folder: &Path,
+    request: &RecordedRequest,
+    response: &RecordedResponse,
+) -> Result<PathBuf, FixtureError> {
+    let path = request.fixture_path(folder);
+    let (request_body, request_base64) = encode_body(request.body);
+    let (response_body, response_base64) = encode_body(&response.body);
+
+    let fixture = Fixture {
+        request: FixtureRequest {
+            method: request.method.to_string(),
+            url: request.url.to_string(),
+            body: request_body,
+            base64: request_base64,
+        },
+        response: FixtureResponse {
+            status: response.status,
+            headers: response.headers.clone(),
+            body: response_body,
+            base64: response_base64,
+        },
+    };
+    let contents =
+        serde_json::to_string_pretty(&fixture).map_err(|error| FixtureError::CannotParse {
+            path: path.clone(),
+            error: error.to_string(),
+        })?;
+
+    fs::create_dir_all(folder)
+        .and_then(|_| fs::write(&path, contents))
+        .map_err(|error| FixtureError::CannotWrite {
+            path: path.clone(),
+            error,
+        })?;
+
+    Ok(path)
+}
+
+/// Load the response of the request from the given folder
+pub(crate) fn load_fixture(
+    folder: &Path,
+    request: &RecordedRequest,
+) -> Result<RecordedResponse, FixtureError> {
+    let path = request.fixture_path(folder);
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(FixtureError::Missing {
+                method: request.method.to_string(),
+                url: request.url.to_string(),
+                path,
+            })
+        }
+        Err(error) => return Err(FixtureError::CannotRead { path, error }),
+    };
+
+    let cannot_parse = |error: String| FixtureError::CannotParse {
+        path: path.clone(),
+        error,
+    };
+    let fixture: Fixture =
+        serde_json::from_str(&contents).map_err(|error| cannot_parse(error.to_string()))?;
+
+    let body = if fixture.response.base64 {
+        general_purpose::STANDARD
+            .decode(fixture.response.body)
+            .map_err(|error| cannot_parse(error.to_string()))?
+    } else {
+        fixture.response.body.into_bytes()
+    };
+
+    Ok(RecordedResponse {
+        status: fixture.response.status,
+        headers: fixture.response.headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn request(body: &[u8]) -> RecordedRequest {
+        RecordedRequest {
+            method: "POST",
+            url: "https://api.example.com/todos?page=1",
+            body,
+        }
+    }
+
+    #[test]
+    fn fixture_path_identifies_the_request() {
+        let folder = Path::new("fixtures");
+        let path = request(b"{}").fixture_path(folder);
+        let name = path.file_name().unwrap().to_string_lossy();
+
+        assert!(name.starts_with("post-api.example.com-"));
+        assert!(name.ends_with(".json"));
+        assert_eq!(path, request(b"{}").fixture_path(folder));
+        assert_ne!(path, request(b"[]").fixture_path(folder));
+    }
+
+    #[test]
+    fn replay_saved_fixtures() {
+        let folder = env::temp_dir().join(format!("wws-fixtures-{}", std::process::id()));
+        let response = RecordedResponse {
+            status: 201,
+            headers: vec![("content-type".to_string(), "image/png".to_string())],
+            body: vec![0, 159, 146, 150],
+        };
+
+        let path = save_fixture(&folder, &request(b"{}"), &response).unwrap();
+        assert!(path.exists());
+        assert_eq!(load_fixture(&folder, &request(b"{}")).unwrap(), response);
+
+        let missing = load_fixture(&folder, &request(b"[]"));
+        assert!(matches!(missing, Err(FixtureError::Missing { .. })));
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
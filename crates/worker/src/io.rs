@@ -8,7 +8,7 @@ use actix_web::{
     HttpRequest,
 };
 use base64::{engine::general_purpose, Engine as _};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 
 /// JSON input for wasm modules. This information is passed via STDIN / WASI
@@ -19,10 +19,13 @@ pub struct WasmInput<'a> {
     url: String,
     /// Request method
     method: &'a str,
-    /// Request headers
-    headers: HashMap<String, String>,
-    /// Request body
-    body: &'a str,
+    /// Request headers. Repeated headers keep all their values in
+    /// the same order, like the response headers
+    headers: HashMap<String, Vec<String>>,
+    /// Request body. Binary bodies are encoded as base64
+    body: String,
+    /// Internal value to indicate if the body is base64 encoded
+    base64: bool,
     /// Key / Value store content if available
     kv: HashMap<String, String>,
     /// The list of parameters in the URL
@@ -31,30 +34,51 @@ pub struct WasmInput<'a> {
 
 impl<'a> WasmInput<'a> {
     /// Generates a new struct to pass the data to wasm module. It's based on the
-    /// HttpRequest, body and the Key / Value store (if available)
+    /// HttpRequest, body, the route parameters and the Key / Value store (if
+    /// available). Bodies that are not valid UTF-8 are encoded as base64
     pub fn new(
         request: &'a HttpRequest,
-        body: &'a str,
+        body: &[u8],
+        params: HashMap<String, String>,
         kv: Option<HashMap<String, String>>,
     ) -> Self {
-        let mut params = HashMap::new();
-
-        for (k, v) in request.match_info().iter() {
-            params.insert(k.to_string(), v.to_string());
-        }
-
         let url = Self::build_url(request);
+        let (body, base64) = match std::str::from_utf8(body) {
+            Ok(body) => (body.to_string(), false),
+            Err(_) => (general_purpose::STANDARD.encode(body), true),
+        };
 
         Self {
             url,
             method: request.method().as_str(),
             headers: Self::build_headers_hash(request.headers()),
             body,
+            base64,
             kv: kv.unwrap_or_default(),
             params,
         }
     }
 
+    /// Apply the given changes to the request headers. A value replaces
+    /// all the previous ones and `None` removes the header. Header names
+    /// are case insensitive.
+    pub fn with_header_changes(mut self, changes: &HashMap<String, Option<String>>) -> Self {
+        for (key, value) in changes {
+            let key = key.to_lowercase();
+
+            match value {
+                Some(value) => {
+                    self.headers.insert(key, vec![value.clone()]);
+                }
+                None => {
+                    self.headers.remove(&key);
+                }
+            }
+        }
+
+        self
+    }
+
     /// Prepare the URL from the given actix HTTP request. It will try to
     /// load the full URL including the authority and the schema. This is
     /// required by different frameworks.
@@ -70,15 +94,19 @@ impl<'a> WasmInput<'a> {
         }
     }
 
-    /// Create HashMap from a HeadersMap
-    fn build_headers_hash(headers: &HeaderMap) -> HashMap<String, String> {
+    /// Create HashMap from a HeadersMap. Every header keeps the list
+    /// of its values. Values that are not valid UTF-8 are converted
+    /// lossily.
+    fn build_headers_hash(headers: &HeaderMap) -> HashMap<String, Vec<String>> {
         let mut parsed_headers = HashMap::new();
 
-        for (key, value) in headers.iter() {
-            parsed_headers.insert(
-                String::from(key.as_str()),
-                String::from(value.to_str().unwrap()),
-            );
+        for key in headers.keys() {
+            let values = headers
+                .get_all(key)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect();
+
+            parsed_headers.insert(String::from(key.as_str()), values);
         }
 
         parsed_headers
@@ -89,8 +117,10 @@ impl<'a> WasmInput<'a> {
 /// from the module.
 #[derive(Deserialize, Debug)]
 pub struct WasmOutput {
-    /// Response headers
-    pub headers: HashMap<String, String>,
+    /// Response headers. Workers can set a header multiple times,
+    /// like `Set-Cookie`, by returning a list of values
+    #[serde(deserialize_with = "read_header_values")]
+    pub headers: HashMap<String, Vec<String>>,
     /// Response HTTP status
     pub status: u16,
     /// New state of the K/V store if available
@@ -100,12 +130,42 @@ pub struct WasmOutput {
     /// Internal value to indicate if the body is base64 encoded
     #[serde(default = "default_base64_encoding")]
     base64: bool,
+    /// The error of a failed run. Workers cannot set it
+    #[serde(skip)]
+    pub error: Option<String>,
 }
 
 fn default_base64_encoding() -> bool {
     false
 }
 
+/// Value of a response header. It can be a single value or a list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HeaderValues {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// Deserialize the response headers. It accepts both strings
+/// and lists of strings as values
+fn read_header_values<'de, D>(
+    deserializer: D,
+) -> core::result::Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let headers: HashMap<String, HeaderValues> = Deserialize::deserialize(deserializer)?;
+
+    Ok(headers
+        .into_iter()
+        .map(|(key, values)| match values {
+            HeaderValues::Single(value) => (key, vec![value]),
+            HeaderValues::Multiple(values) => (key, values),
+        })
+        .collect())
+}
+
 impl WasmOutput {
     /// Initializes a new WasmOutput object
     pub fn new(
@@ -117,26 +177,43 @@ impl WasmOutput {
         Self {
             data: String::from(body),
             base64: false,
-            headers,
+            headers: headers
+                .into_iter()
+                .map(|(key, value)| (key, vec![value]))
+                .collect(),
             status,
             kv,
+            error: None,
         }
     }
 
     /// Build a default WasmOutput for a failed run. It will
-    /// return a generic error message and the proper 500
-    /// status code
+    /// return a generic error message and the proper status code.
+    /// Workers interrupted by their limits return a 504, the rest
+    /// of errors a 500. The output keeps the error details.
     pub fn failed(err: WorkerError, worker_name: Option<String>, route: Option<String>) -> Self {
         eprintln!(
             "Error running worker {:?} at route {:?}: {err}",
             worker_name, route
         );
-        Self::new(
-            "<p>There was an error running this function</p>",
-            HashMap::from([("content-type".to_string(), "text/html".to_string())]),
-            StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-            HashMap::new(),
-        )
+
+        let error = err.to_string();
+        let status = match err {
+            WorkerError::Timeout { .. } | WorkerError::OutOfFuel { .. } => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        Self {
+            error: Some(error),
+            ..Self::new(
+                "<p>There was an error running this function</p>",
+                HashMap::from([("content-type".to_string(), "text/html".to_string())]),
+                status.as_u16(),
+                HashMap::new(),
+            )
+        }
     }
 
     /// Return the content body as bytes. It will automatically
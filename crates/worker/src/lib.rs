@@ -2,32 +2,45 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod bindings;
+pub mod cache;
 pub mod config;
+mod engine;
 pub mod errors;
 pub mod features;
+pub mod fixtures;
 pub mod io;
+pub mod limits;
 mod stdio;
 
 use actix_web::HttpRequest;
 use bindings::http::{add_to_linker as http_add_to_linker, HttpBindings};
+use bindings::kv::{add_to_linker as kv_add_to_linker, KvBindings};
+use bindings::sql::{add_to_linker as sql_add_to_linker, SqlBindings};
 use config::Config;
 use errors::Result;
+use fixtures::HttpMode;
 use io::{WasmInput, WasmOutput};
+use limits::{LimitExceeded, ResourceCounts, WorkerLimiter};
 use sha256::digest as sha256_digest;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{collections::HashMap, path::Path};
-use stdio::Stdio;
+use stdio::{Stdio, DEFAULT_MAX_OUTPUT_BYTES};
+use tokio::time::timeout;
 use wasmtime::{
     component::{self, Component},
-    Config as WasmtimeConfig, Engine, Linker, Module, Store,
+    Engine, Linker, Module, Store, Trap,
 };
 use wasmtime_wasi::{ambient_authority, preview2, Dir, WasiCtxBuilder};
 use wasmtime_wasi_nn::{InMemoryRegistry, Registry, WasiNnCtx};
 use wws_config::Config as ProjectConfig;
+use wws_data_kv::KVStore;
+use wws_data_sql::SQLDatabase;
 use wws_runtimes::{init_runtime, CtxBuilder, Runtime};
 
+#[derive(Clone)]
 pub enum ModuleOrComponent {
     Module(Module),
     Component(Component),
@@ -39,18 +52,44 @@ pub enum ModuleOrComponent {
 pub struct Worker {
     /// Worker identifier
     pub id: String,
-    /// Wasmtime engine to run this worker
+    /// Wasmtime engine to run this worker. It's shared by all workers
     engine: Engine,
     /// Worker runtime
     runtime: Box<dyn Runtime + Sync + Send>,
     /// Wasm Module or component
     module_or_component: ModuleOrComponent,
+    /// Instances and tables the module or component creates
+    resources: ResourceCounts,
+    /// Identifier of the compiled module or component in the cache
+    cache_key: String,
     /// Current config
     pub config: Config,
     /// The worker filepath
     path: PathBuf,
 }
 
+/// The data connectors a worker can access while it processes a request
+#[derive(Default)]
+pub struct WorkerData {
+    /// A snapshot of the K/V namespace, sent in the worker input
+    pub kv: Option<HashMap<String, String>>,
+    /// The K/V store the host functions access
+    pub kv_store: Option<Arc<KVStore>>,
+    /// The SQL databases the host functions can access
+    pub databases: HashMap<String, Arc<SQLDatabase>>,
+}
+
+/// The request a worker processes. Middlewares may change its headers
+/// and parameters before the route worker receives it
+pub struct WorkerRequest<'a> {
+    pub request: &'a HttpRequest,
+    pub body: &'a [u8],
+    /// The list of parameters in the URL
+    pub params: HashMap<String, String>,
+    /// Changes to the headers of the request. A `None` value removes the header
+    pub headers: HashMap<String, Option<String>>,
+}
+
 #[derive(Default)]
 struct Host {
     pub wasi_preview1_ctx: Option<wasmtime_wasi::WasiCtx>,
@@ -67,6 +106,9 @@ struct Host {
 
     pub wasi_nn: Option<Arc<WasiNnCtx>>,
     pub http: Option<HttpBindings>,
+    pub kv: Option<KvBindings>,
+    pub sql: Option<SqlBindings>,
+    pub limiter: WorkerLimiter,
 }
 
 impl preview2::WasiView for Host {
@@ -120,36 +162,27 @@ impl Worker {
             }
         }
 
-        let engine = Engine::new(
-            WasmtimeConfig::default()
-                .async_support(true)
-                .wasm_component_model(true),
-        )
-        .map_err(|err| errors::WorkerError::ConfigureRuntimeError {
-            error: format!("error creating engine ({err})"),
-        })?;
+        // Paths in the worker configuration are relative to its folder and
+        // the ones in the project configuration to the project root
+        let worker_folder = project_root.join(path);
+        let worker_folder = worker_folder.parent().unwrap_or(project_root);
+        let http_defaults = project_config
+            .http_requests
+            .clone()
+            .unwrap_or_default()
+            .with_base_path(project_root);
+        let network = &mut config.features.http_requests.network;
+        *network = std::mem::take(network)
+            .with_base_path(worker_folder)
+            .or(&http_defaults);
+
+        let engine = engine::shared_engine(config.limits.fuel.is_some())?;
         let runtime = init_runtime(project_root, path, project_config)?;
         let bytes = runtime.module_bytes()?;
-
-        let module_or_component = if wasmparser::Parser::is_core_wasm(&bytes) {
-            Ok(ModuleOrComponent::Module(
-                Module::from_binary(&engine, &bytes).map_err(|err| {
-                    errors::WorkerError::BadWasmCoreModule {
-                        error: format!("{:?}", err),
-                    }
-                })?,
-            ))
-        } else if wasmparser::Parser::is_component(&bytes) {
-            Ok(ModuleOrComponent::Component(
-                Component::from_binary(&engine, &bytes).map_err(|err| {
-                    errors::WorkerError::BadWasmComponent {
-                        error: format!("{:?}", err),
-                    }
-                })?,
-            ))
-        } else {
-            Err(errors::WorkerError::BadWasmCoreModuleOrComponent)
-        }?;
+        let cache_key = cache::cache_key(&engine, &bytes);
+        let module_or_component =
+            cache::load_module_or_component(project_root, &engine, &bytes, &cache_key)?;
+        let resources = ResourceCounts::from_binary(&bytes);
 
         // Prepare the environment if required
         runtime.prepare()?;
@@ -159,11 +192,19 @@ impl Worker {
             engine,
             runtime,
             module_or_component,
+            resources,
+            cache_key,
             config,
             path: path.to_path_buf(),
         })
     }
 
+    /// Identifier of the compiled module or component of the worker.
+    /// Workers that run the same Wasm binary share it
+    pub fn cache_key(&self) -> &str {
+        &self.cache_key
+    }
+
     pub fn prepare_wasi_context(
         &self,
         environment_variables: &[(String, String)],
@@ -239,14 +280,47 @@ impl Worker {
     /// Run the worker that will process the given URL. This method sets the
     /// module context including all the required features like WASI and WASI-NN.
     /// Then, it loads the module, run it and process the output.
+    ///
+    /// The K/V data can be sent as a snapshot in the input or through
+    /// the K/V store handle, which is available from the host functions.
+    /// The SQL host functions can only access the given databases.
+    /// The HTTP requests of the worker follow the given `http_mode`.
     pub async fn run(
         &self,
-        request: &HttpRequest,
-        body: &str,
-        kv: Option<HashMap<String, String>>,
+        request: WorkerRequest<'_>,
+        data: WorkerData,
         vars: &HashMap<String, String>,
+        http_mode: &HttpMode,
     ) -> Result<WasmOutput> {
-        let input = serde_json::to_string(&WasmInput::new(request, body, kv)).unwrap();
+        let execution = self.execute(request, data, vars, http_mode);
+
+        // The epoch deadline only interrupts the worker while it runs Wasm
+        // code. This timeout also covers the time it waits for the host,
+        // like the HTTP requests.
+        match self.config.limits.timeout_ms {
+            Some(timeout_ms) => timeout(Duration::from_millis(timeout_ms), execution)
+                .await
+                .unwrap_or(Err(errors::WorkerError::Timeout { timeout_ms })),
+            None => execution.await,
+        }
+    }
+
+    /// Prepare the context of the worker, run it and read its output
+    async fn execute(
+        &self,
+        request: WorkerRequest<'_>,
+        data: WorkerData,
+        vars: &HashMap<String, String>,
+        http_mode: &HttpMode,
+    ) -> Result<WasmOutput> {
+        let WorkerData {
+            kv,
+            kv_store,
+            databases,
+        } = data;
+        let input = WasmInput::new(request.request, request.body, request.params, kv)
+            .with_header_changes(&request.headers);
+        let input = serde_json::to_string(&input).unwrap();
 
         let mut linker = Linker::new(&self.engine);
         let mut component_linker = component::Linker::new(&self.engine);
@@ -263,6 +337,18 @@ impl Worker {
                 .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
                     error: format!("error adding HTTP bindings to linker ({error})"),
                 })?;
+
+            kv_add_to_linker(&mut linker, |host: &mut Host| host.kv.as_mut().unwrap()).map_err(
+                |error| errors::WorkerError::ConfigureRuntimeError {
+                    error: format!("error adding K/V bindings to linker ({error})"),
+                },
+            )?;
+
+            sql_add_to_linker(&mut linker, |host: &mut Host| host.sql.as_mut().unwrap()).map_err(
+                |error| errors::WorkerError::ConfigureRuntimeError {
+                    error: format!("error adding SQL bindings to linker ({error})"),
+                },
+            )?;
         } else {
             preview2::command::add_to_linker(&mut component_linker).map_err(|error| {
                 errors::WorkerError::ConfigureRuntimeError {
@@ -281,7 +367,13 @@ impl Worker {
         };
         self.prepare_wasi_context(&environment_variables, &mut wasi_builder)?;
 
-        let stdio = Stdio::new(&input);
+        let stdio = Stdio::new(
+            &input,
+            self.config
+                .limits
+                .max_response_bytes
+                .unwrap_or(DEFAULT_MAX_OUTPUT_BYTES),
+        );
         let mut wasi_builder = stdio.configure_wasi_ctx(wasi_builder);
 
         self.runtime.prepare_wasi_ctx(&mut wasi_builder)?;
@@ -319,6 +411,13 @@ impl Worker {
             None
         };
 
+        let limiter = WorkerLimiter::new(&self.config.limits);
+        limiter.check_resources(&self.resources).map_err(|limit| {
+            errors::WorkerError::ResourceLimitExceeded {
+                limit: limit.to_string(),
+            }
+        })?;
+
         let host = match wasi_builder {
             CtxBuilder::Preview1(mut wasi_builder) => {
                 if wasi_nn.is_some() {
@@ -336,9 +435,13 @@ impl Worker {
                 Host {
                     wasi_preview1_ctx: Some(wasi_builder.build()),
                     wasi_nn,
-                    http: Some(HttpBindings {
-                        http_config: self.config.features.http_requests.clone(),
-                    }),
+                    http: Some(HttpBindings::new(
+                        self.config.features.http_requests.clone(),
+                        http_mode.clone(),
+                    )),
+                    kv: Some(KvBindings { store: kv_store }),
+                    sql: Some(SqlBindings { databases }),
+                    limiter,
                     ..Host::default()
                 }
             }
@@ -368,23 +471,38 @@ impl Worker {
                         preview2::preview1::WasiPreview1Adapter::default(),
                     ),
                     wasi_nn,
-                    http: Some(HttpBindings {
-                        http_config: self.config.features.http_requests.clone(),
-                    }),
+                    http: Some(HttpBindings::new(
+                        self.config.features.http_requests.clone(),
+                        http_mode.clone(),
+                    )),
+                    kv: Some(KvBindings { store: kv_store }),
+                    sql: Some(SqlBindings { databases }),
+                    limiter,
                     ..Host::default()
                 }
             }
         };
 
-        let contents = {
-            let mut store = Store::new(&self.engine, host);
-            match &self.module_or_component {
-                ModuleOrComponent::Module(module) => {
+        let mut store = Store::new(&self.engine, host);
+        store.set_epoch_deadline(engine::epoch_deadline(self.config.limits.timeout_ms));
+        store.limiter(|host| &mut host.limiter);
+
+        if let Some(fuel) = self.config.limits.fuel {
+            store
+                .add_fuel(fuel)
+                .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
+                    error: format!("error setting the worker fuel: {error}"),
+                })?;
+        }
+
+        let result = match &self.module_or_component {
+            ModuleOrComponent::Module(module) => {
+                async {
                     linker
                         .module_async(&mut store, "", module)
                         .await
-                        .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
-                            error: format!("error retrieving module from linker: {error}"),
+                        .map_err(|error| {
+                            self.run_error(error, "error retrieving module from linker")
                         })?;
 
                     linker
@@ -400,19 +518,14 @@ impl Worker {
                         })?
                         .call_async(&mut store, ())
                         .await
-                        .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
-                            error: format!("error calling module default export: {error}"),
-                        })?;
-
-                    drop(store);
-
-                    stdio
-                        .stdout
-                        .try_into_inner()
-                        .unwrap_or_default()
-                        .into_inner()
+                        .map_err(|error| {
+                            self.run_error(error, "error calling module default export")
+                        })
                 }
-                ModuleOrComponent::Component(component) => {
+                .await
+            }
+            ModuleOrComponent::Component(component) => {
+                async {
                     let (command, _instance) = preview2::command::Command::instantiate_async(
                         &mut store,
                         component,
@@ -420,26 +533,31 @@ impl Worker {
                     )
                     .await
                     .map_err(|error| {
-                        errors::WorkerError::ConfigureRuntimeError {
-                            error: format!("error instantiating component cli::run: {error}"),
-                        }
+                        self.run_error(error, "error instantiating component cli::run")
                     })?;
 
                     let _ = command
                         .wasi_cli_run()
                         .call_run(&mut store)
                         .await
-                        .map_err(|error| errors::WorkerError::ConfigureRuntimeError {
-                            error: format!("error calling component cli::run: {error}"),
+                        .map_err(|error| {
+                            self.run_error(error, "error calling component cli::run")
                         })?;
 
-                    drop(store);
-
-                    stdio.stdout_preview2.contents().to_vec()
+                    Ok::<(), errors::WorkerError>(())
                 }
+                .await
             }
         };
 
+        // The output must be checked before the result. Writing over
+        // the limit usually makes the worker fail.
+        drop(store);
+        let contents = stdio
+            .output()
+            .map_err(|limit| errors::WorkerError::ResponseTooLarge { limit })?;
+        result?;
+
         // Build the output
         let output: WasmOutput = serde_json::from_slice(&contents).map_err(|error| {
             errors::WorkerError::ConfigureRuntimeError {
@@ -449,4 +567,33 @@ impl Worker {
 
         Ok(output)
     }
+
+    /// Map an error from running the worker. The traps caused by the
+    /// execution limits get their own error, so the server can reply
+    /// with the right status code.
+    fn run_error(&self, error: wasmtime::Error, context: &str) -> errors::WorkerError {
+        match error.downcast_ref::<Trap>() {
+            Some(Trap::Interrupt) => {
+                return errors::WorkerError::Timeout {
+                    timeout_ms: self.config.limits.timeout_ms.unwrap_or_default(),
+                }
+            }
+            Some(Trap::OutOfFuel) => {
+                return errors::WorkerError::OutOfFuel {
+                    fuel: self.config.limits.fuel.unwrap_or_default(),
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(limit) = error.downcast_ref::<LimitExceeded>() {
+            return errors::WorkerError::ResourceLimitExceeded {
+                limit: limit.to_string(),
+            };
+        }
+
+        errors::WorkerError::ConfigureRuntimeError {
+            error: format!("{context}: {error}"),
+        }
+    }
 }
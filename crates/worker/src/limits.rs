@@ -0,0 +1,277 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Deserialize;
+use std::fmt;
+use wasmparser::{Instance, Parser, Payload};
+use wasmtime::{
+    ResourceLimiter, DEFAULT_INSTANCE_LIMIT, DEFAULT_MEMORY_LIMIT, DEFAULT_TABLE_LIMIT,
+};
+
+/// Number of bytes in a megabyte
+const MB: usize = 1024 * 1024;
+
+/// Execution limits for a worker. They are configured in the `[limits]`
+/// section of the worker TOML file. By default, workers run without limits,
+/// except for the size of their output.
+///
+/// # Examples
+///
+/// ```
+/// name = "api"
+/// version = "1"
+///
+/// [limits]
+/// timeout_ms = 500
+/// fuel = 100000000
+/// max_memory_mb = 64
+/// max_tables = 10
+/// max_instances = 10
+/// max_response_bytes = 1048576
+/// ```
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Maximum time in milliseconds the worker can run to process a request.
+    /// The run is interrupted after it, with a precision of a few milliseconds.
+    /// It includes the time the worker waits for the host, like HTTP requests
+    pub timeout_ms: Option<u64>,
+    /// Maximum amount of fuel the worker can consume to process a request.
+    /// Every Wasm instruction consumes fuel, so this limit is deterministic
+    pub fuel: Option<u64>,
+    /// Maximum size in megabytes of the linear memories of the worker
+    pub max_memory_mb: Option<u64>,
+    /// Maximum number of tables the worker can create
+    pub max_tables: Option<usize>,
+    /// Maximum number of instances the worker can create. Components
+    /// create one instance per core module they contain
+    pub max_instances: Option<usize>,
+    /// Maximum size in bytes of the output of the worker. It's 64MB by default
+    pub max_response_bytes: Option<usize>,
+}
+
+/// Error returned when a worker tries to grow beyond its limits. It
+/// traps the worker, so it can be distinguished from other errors.
+#[derive(Debug)]
+pub struct LimitExceeded {
+    /// Description of the exceeded limit
+    pub limit: String,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.limit)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Number of instances and tables a worker creates when it runs. They
+/// don't change between runs, so they are counted once from the binary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceCounts {
+    pub instances: usize,
+    pub tables: usize,
+}
+
+impl ResourceCounts {
+    /// Count the resources of a Wasm module or component. A module is a
+    /// single instance. For components, every core module is counted
+    /// once, as the toolchains instantiate them once.
+    pub fn from_binary(bytes: &[u8]) -> Self {
+        let mut counts = Self::default();
+
+        for payload in Parser::new(0).parse_all(bytes) {
+            match payload {
+                Ok(Payload::TableSection(reader)) => {
+                    counts.tables += usize::try_from(reader.count()).unwrap_or(usize::MAX);
+                }
+                Ok(Payload::CoreInstanceSection(reader)) => {
+                    counts.instances += reader
+                        .into_iter()
+                        .filter(|instance| matches!(instance, Ok(Instance::Instantiate { .. })))
+                        .count();
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+
+        if Parser::is_core_wasm(bytes) {
+            counts.instances = 1;
+        }
+
+        counts
+    }
+}
+
+/// Enforce the memory, table and instance limits of a worker. Wasmtime
+/// calls it every time the worker creates or grows a resource.
+pub struct WorkerLimiter {
+    /// Maximum size in bytes of all the linear memories
+    max_memory: Option<usize>,
+    /// Current size in bytes of all the linear memories
+    memory: usize,
+    /// Maximum number of tables
+    max_tables: usize,
+    /// Maximum number of instances
+    max_instances: usize,
+}
+
+impl WorkerLimiter {
+    /// Initialize a limiter for the given configuration
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            max_memory: config
+                .max_memory_mb
+                .map(|mb| usize::try_from(mb).unwrap_or(usize::MAX).saturating_mul(MB)),
+            memory: 0,
+            max_tables: config.max_tables.unwrap_or(DEFAULT_TABLE_LIMIT),
+            max_instances: config.max_instances.unwrap_or(DEFAULT_INSTANCE_LIMIT),
+        }
+    }
+
+    /// Check the worker doesn't create more instances and tables than
+    /// allowed. It runs before instantiating the worker, so it fails
+    /// with the same error as the rest of the limits.
+    pub fn check_resources(&self, counts: &ResourceCounts) -> Result<(), LimitExceeded> {
+        if counts.instances > self.max_instances {
+            return Err(LimitExceeded {
+                limit: format!(
+                    "instance limit of {} exceeded with {} instances",
+                    self.max_instances, counts.instances
+                ),
+            });
+        }
+
+        if counts.tables > self.max_tables {
+            return Err(LimitExceeded {
+                limit: format!(
+                    "table limit of {} exceeded with {} tables",
+                    self.max_tables, counts.tables
+                ),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for WorkerLimiter {
+    fn default() -> Self {
+        Self::new(&LimitsConfig::default())
+    }
+}
+
+impl ResourceLimiter for WorkerLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if maximum.is_some_and(|maximum| desired > maximum) {
+            return Ok(false);
+        }
+
+        let memory = self.memory.saturating_sub(current).saturating_add(desired);
+
+        if let Some(max_memory) = self.max_memory {
+            if memory > max_memory {
+                return Err(LimitExceeded {
+                    limit: format!(
+                        "memory limit of {}MB exceeded when growing to {desired} bytes",
+                        max_memory / MB
+                    ),
+                }
+                .into());
+            }
+        }
+
+        self.memory = memory;
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> wasmtime::Result<bool> {
+        Ok(!maximum.is_some_and(|maximum| desired > maximum))
+    }
+
+    fn instances(&self) -> usize {
+        self.max_instances
+    }
+
+    fn tables(&self) -> usize {
+        self.max_tables
+    }
+
+    fn memories(&self) -> usize {
+        DEFAULT_MEMORY_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_limit_is_typed() {
+        let mut limiter = WorkerLimiter::new(&LimitsConfig {
+            max_memory_mb: Some(1),
+            ..LimitsConfig::default()
+        });
+
+        assert!(limiter.memory_growing(0, MB, None).unwrap());
+
+        let error = limiter.memory_growing(MB, 2 * MB, None).unwrap_err();
+        assert!(error.downcast_ref::<LimitExceeded>().is_some());
+    }
+
+    #[test]
+    fn check_resource_counts() {
+        let limiter = WorkerLimiter::new(&LimitsConfig {
+            max_instances: Some(2),
+            max_tables: Some(1),
+            ..LimitsConfig::default()
+        });
+
+        assert!(limiter
+            .check_resources(&ResourceCounts {
+                instances: 2,
+                tables: 1
+            })
+            .is_ok());
+        assert!(limiter
+            .check_resources(&ResourceCounts {
+                instances: 3,
+                tables: 1
+            })
+            .is_err());
+        assert!(limiter
+            .check_resources(&ResourceCounts {
+                instances: 1,
+                tables: 2
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn count_module_resources() {
+        // A module with a single table: (module (table 1 funcref))
+        let module = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x01,
+        ];
+
+        assert_eq!(
+            ResourceCounts::from_binary(&module),
+            ResourceCounts {
+                instances: 1,
+                tables: 1
+            }
+        );
+    }
+}
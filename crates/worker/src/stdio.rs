@@ -1,14 +1,38 @@
-use std::io::Cursor;
+use std::io::{self, Write};
 use wasi_common::pipe::{ReadPipe, WritePipe};
 use wasmtime_wasi::preview2;
 use wws_runtimes::CtxBuilder;
 
-// WASI's Preview2 MemoryOutputPipe, used to retrieve the result from
-// the worker, requires a `capacity` setting
-// (https://docs.rs/wasmtime-wasi/13.0.0/wasmtime_wasi/preview2/pipe/struct.MemoryOutputPipe.html#method.new). We
-// might look into a solution that is less restrictive than this. This
-// effectively caps the output size a worker can produce.
-const MAX_OUTPUT_BYTES: usize = 10240;
+/// Maximum size in bytes of the output of the workers that don't set the
+/// `max_response_bytes` limit. The output is kept in memory until the
+/// worker finishes, so it always needs a limit.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024 * 1024;
+
+/// In-memory stdout for the preview1 workers. The writes fail when the
+/// buffer is full, like the preview2 `MemoryOutputPipe`.
+struct OutputBuffer {
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let available = self.capacity - self.buffer.len();
+
+        if available == 0 && !buf.is_empty() {
+            return Err(io::Error::other("the worker output is too large"));
+        }
+
+        let len = buf.len().min(available);
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
 /// A library to configure the stdio of the WASI context.
 /// Note that currently, wws relies on stdin and stdout
@@ -20,18 +44,45 @@ pub struct Stdio {
     /// Defines the stdin ReadPipe to send data to the module
     pub stdin: Vec<u8>,
     /// Defines the stdout to extract data from the module
-    pub stdout: WritePipe<Cursor<Vec<u8>>>,
+    stdout: WritePipe<OutputBuffer>,
     /// Defines the stdout to extract data from the module
-    pub stdout_preview2: preview2::pipe::MemoryOutputPipe,
+    stdout_preview2: preview2::pipe::MemoryOutputPipe,
+    /// Maximum size in bytes of the output
+    limit: usize,
 }
 
 impl Stdio {
-    /// Initialize the stdio. The stdin will contain the input data.
-    pub fn new(input: &str) -> Self {
+    /// Initialize the stdio. The stdin will contain the input data. The
+    /// stdout keeps one byte over the limit, so a larger output can be
+    /// detected after the worker finishes.
+    pub fn new(input: &str, limit: usize) -> Self {
+        let capacity = limit.saturating_add(1);
+
         Self {
             stdin: Vec::from(input),
-            stdout: WritePipe::new_in_memory(),
-            stdout_preview2: preview2::pipe::MemoryOutputPipe::new(MAX_OUTPUT_BYTES),
+            stdout: WritePipe::new(OutputBuffer {
+                buffer: Vec::new(),
+                capacity,
+            }),
+            stdout_preview2: preview2::pipe::MemoryOutputPipe::new(capacity),
+            limit,
+        }
+    }
+
+    /// Returns the output of the worker. It fails if the output is over
+    /// the limit. Call it once the WASI context is dropped.
+    pub fn output(self) -> Result<Vec<u8>, usize> {
+        // Only one of the pipes is connected to the worker
+        let mut output = match self.stdout.try_into_inner() {
+            Ok(stdout) => stdout.buffer,
+            Err(_) => Vec::new(),
+        };
+        output.extend_from_slice(&self.stdout_preview2.contents());
+
+        if output.len() > self.limit {
+            Err(self.limit)
+        } else {
+            Ok(output)
         }
     }
 
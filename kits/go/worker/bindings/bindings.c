@@ -3,6 +3,16 @@
 
 __attribute__((__import_module__("http"), __import_name__("send-http-request"))) void __wasm_import_http_send_http_request(int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t);
 
+__attribute__((__import_module__("kv"), __import_name__("get"))) void __wasm_import_kv_get(int32_t, int32_t, int32_t);
+
+__attribute__((__import_module__("kv"), __import_name__("set"))) void __wasm_import_kv_set(int32_t, int32_t, int32_t, int32_t, int32_t);
+
+__attribute__((__import_module__("kv"), __import_name__("set-with-ttl"))) void __wasm_import_kv_set_with_ttl(int32_t, int32_t, int32_t, int32_t, int64_t, int32_t);
+
+__attribute__((__import_module__("kv"), __import_name__("delete"))) void __wasm_import_kv_delete(int32_t, int32_t, int32_t);
+
+__attribute__((__import_module__("kv"), __import_name__("list-keys"))) void __wasm_import_kv_list_keys(int32_t);
+
 __attribute__((__weak__, __export_name__("canonical_abi_realloc"))) void *cabi_realloc(void *ptr, size_t old_size, size_t align, size_t new_size)
 {
   if (new_size == 0)
@@ -126,6 +136,26 @@ void bindings_result_http_response_http_request_error_free(bindings_result_http_
   }
 }
 
+void bindings_option_string_free(bindings_option_string_t *ptr)
+{
+  if (ptr->is_some)
+  {
+    bindings_string_free(&ptr->val);
+  }
+}
+
+void bindings_list_string_free(bindings_list_string_t *ptr)
+{
+  for (size_t i = 0; i < ptr->len; i++)
+  {
+    bindings_string_free(&ptr->ptr[i]);
+  }
+  if (ptr->len > 0)
+  {
+    free(ptr->ptr);
+  }
+}
+
 void bindings_string_set(bindings_string_t *ret, const char *s)
 {
   ret->ptr = (char *)s;
@@ -216,6 +246,111 @@ void http_send_http_request(http_interface_http_request_t *request, bindings_res
   *ret = result;
 }
 
+void kv_get(bindings_string_t *key, bindings_option_string_t *ret)
+{
+  __attribute__((__aligned__(4)))
+  uint8_t ret_area[12];
+  int32_t ptr = (int32_t)&ret_area;
+  __wasm_import_kv_get((int32_t)(*key).ptr, (int32_t)(*key).len, ptr);
+  bindings_option_string_t option;
+  switch ((int32_t)(*((uint8_t *)(ptr + 0))))
+  {
+  case 0:
+  {
+    option.is_some = false;
+    break;
+  }
+  case 1:
+  {
+    option.is_some = true;
+    option.val = (bindings_string_t){(char *)(*((int32_t *)(ptr + 4))), (size_t)(*((int32_t *)(ptr + 8)))};
+    break;
+  }
+  }
+  *ret = option;
+}
+
+void kv_set(bindings_string_t *key, bindings_string_t *value, bindings_result_void_kv_error_t *ret)
+{
+  __attribute__((__aligned__(1)))
+  uint8_t ret_area[2];
+  int32_t ptr = (int32_t)&ret_area;
+  __wasm_import_kv_set((int32_t)(*key).ptr, (int32_t)(*key).len, (int32_t)(*value).ptr, (int32_t)(*value).len, ptr);
+  bindings_result_void_kv_error_t result;
+  switch ((int32_t)(*((uint8_t *)(ptr + 0))))
+  {
+  case 0:
+  {
+    result.is_err = false;
+    break;
+  }
+  case 1:
+  {
+    result.is_err = true;
+    result.val.err = (int32_t)(*((uint8_t *)(ptr + 1)));
+    break;
+  }
+  }
+  *ret = result;
+}
+
+void kv_set_with_ttl(bindings_string_t *key, bindings_string_t *value, uint64_t ttl_secs, bindings_result_void_kv_error_t *ret)
+{
+  __attribute__((__aligned__(1)))
+  uint8_t ret_area[2];
+  int32_t ptr = (int32_t)&ret_area;
+  __wasm_import_kv_set_with_ttl((int32_t)(*key).ptr, (int32_t)(*key).len, (int32_t)(*value).ptr, (int32_t)(*value).len, (int64_t)(ttl_secs), ptr);
+  bindings_result_void_kv_error_t result;
+  switch ((int32_t)(*((uint8_t *)(ptr + 0))))
+  {
+  case 0:
+  {
+    result.is_err = false;
+    break;
+  }
+  case 1:
+  {
+    result.is_err = true;
+    result.val.err = (int32_t)(*((uint8_t *)(ptr + 1)));
+    break;
+  }
+  }
+  *ret = result;
+}
+
+void kv_delete(bindings_string_t *key, bindings_result_void_kv_error_t *ret)
+{
+  __attribute__((__aligned__(1)))
+  uint8_t ret_area[2];
+  int32_t ptr = (int32_t)&ret_area;
+  __wasm_import_kv_delete((int32_t)(*key).ptr, (int32_t)(*key).len, ptr);
+  bindings_result_void_kv_error_t result;
+  switch ((int32_t)(*((uint8_t *)(ptr + 0))))
+  {
+  case 0:
+  {
+    result.is_err = false;
+    break;
+  }
+  case 1:
+  {
+    result.is_err = true;
+    result.val.err = (int32_t)(*((uint8_t *)(ptr + 1)));
+    break;
+  }
+  }
+  *ret = result;
+}
+
+void kv_list_keys(bindings_list_string_t *ret)
+{
+  __attribute__((__aligned__(4)))
+  uint8_t ret_area[8];
+  int32_t ptr = (int32_t)&ret_area;
+  __wasm_import_kv_list_keys(ptr);
+  *ret = (bindings_list_string_t){(bindings_string_t *)(*((int32_t *)(ptr + 0))), (size_t)(*((int32_t *)(ptr + 4)))};
+}
+
 extern void __component_type_object_force_link_bindings(void);
 void __component_type_object_force_link_bindings_public_use_in_this_compilation_unit(void)
 {
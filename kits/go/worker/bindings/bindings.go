@@ -78,6 +78,9 @@ HttpTypesHttpErrorKindNotAllowed
 HttpTypesHttpErrorKindInternalError
 HttpTypesHttpErrorKindTimeout
 HttpTypesHttpErrorKindRedirectLoop
+HttpTypesHttpErrorKindTooManyRedirects
+HttpTypesHttpErrorKindResponseTooLarge
+HttpTypesHttpErrorKindTooManyRequests
 )
 
 type HttpTypesHttpError struct {
@@ -116,6 +119,18 @@ func HttpTypesHttpErrorRedirectLoop() HttpTypesHttpError{
   return HttpTypesHttpError{kind: HttpTypesHttpErrorKindRedirectLoop}
 }
 
+func HttpTypesHttpErrorTooManyRedirects() HttpTypesHttpError{
+  return HttpTypesHttpError{kind: HttpTypesHttpErrorKindTooManyRedirects}
+}
+
+func HttpTypesHttpErrorResponseTooLarge() HttpTypesHttpError{
+  return HttpTypesHttpError{kind: HttpTypesHttpErrorKindResponseTooLarge}
+}
+
+func HttpTypesHttpErrorTooManyRequests() HttpTypesHttpError{
+  return HttpTypesHttpError{kind: HttpTypesHttpErrorKindTooManyRequests}
+}
+
 type HttpTypesHttpRequestError struct {
   Error HttpTypesHttpError
   Message string
@@ -277,6 +292,15 @@ func HttpSendHttpRequest(request HttpTypesHttpRequest) Result[HttpTypesHttpRespo
     if lift_ret_ptr.error == 6 {
       lift_ret_val_val_Error = HttpTypesHttpErrorRedirectLoop()
     }
+    if lift_ret_ptr.error == 7 {
+      lift_ret_val_val_Error = HttpTypesHttpErrorTooManyRedirects()
+    }
+    if lift_ret_ptr.error == 8 {
+      lift_ret_val_val_Error = HttpTypesHttpErrorResponseTooLarge()
+    }
+    if lift_ret_ptr.error == 9 {
+      lift_ret_val_val_Error = HttpTypesHttpErrorTooManyRequests()
+    }
     lift_ret_val_val.Error = lift_ret_val_val_Error
     var lift_ret_val_val_Message string
     lift_ret_val_val_Message = C.GoStringN(lift_ret_ptr.message.ptr, C.int(lift_ret_ptr.message.len))
@@ -335,3 +359,164 @@ func HttpSendHttpRequest(request HttpTypesHttpRequest) Result[HttpTypesHttpRespo
   return lift_ret
 }
 
+// kv
+type KvKvErrorKind int
+
+const (
+KvKvErrorKindNotAvailable KvKvErrorKind = iota
+KvKvErrorKindStorageError
+KvKvErrorKindQuotaExceeded
+)
+
+type KvKvError struct {
+  kind KvKvErrorKind
+}
+
+func (n KvKvError) Kind() KvKvErrorKind {
+  return n.kind
+}
+
+func KvKvErrorNotAvailable() KvKvError{
+  return KvKvError{kind: KvKvErrorKindNotAvailable}
+}
+
+func KvKvErrorStorageError() KvKvError{
+  return KvKvError{kind: KvKvErrorKindStorageError}
+}
+
+func KvKvErrorQuotaExceeded() KvKvError{
+  return KvKvError{kind: KvKvErrorKindQuotaExceeded}
+}
+
+func KvGet(key string) Option[string] {
+  var lower_key C.bindings_string_t
+  
+  lower_key.ptr = C.CString(key)
+  lower_key.len = C.size_t(len(key))
+  defer C.bindings_string_free(&lower_key)
+  var ret C.bindings_option_string_t
+  C.kv_get(&lower_key, &ret)
+  var lift_ret Option[string]
+  if ret.is_some {
+    var lift_ret_val string
+    lift_ret_val = C.GoStringN(ret.val.ptr, C.int(ret.val.len))
+    lift_ret.Set(lift_ret_val)
+  } else {
+    lift_ret.Unset()
+  }
+  return lift_ret
+}
+
+func KvSet(key string, value string) Result[struct{}, KvKvError] {
+  var lower_key C.bindings_string_t
+  
+  lower_key.ptr = C.CString(key)
+  lower_key.len = C.size_t(len(key))
+  defer C.bindings_string_free(&lower_key)
+  var lower_value C.bindings_string_t
+  
+  lower_value.ptr = C.CString(value)
+  lower_value.len = C.size_t(len(value))
+  defer C.bindings_string_free(&lower_value)
+  var ret C.bindings_result_void_kv_error_t
+  C.kv_set(&lower_key, &lower_value, &ret)
+  var lift_ret Result[struct{}, KvKvError]
+  if ret.is_err {
+    lift_ret_ptr := *(*C.kv_interface_kv_error_t)(unsafe.Pointer(&ret.val))
+    var lift_ret_val KvKvError
+    if lift_ret_ptr == 0 {
+      lift_ret_val = KvKvErrorNotAvailable()
+    }
+    if lift_ret_ptr == 1 {
+      lift_ret_val = KvKvErrorStorageError()
+    }
+    if lift_ret_ptr == 2 {
+      lift_ret_val = KvKvErrorQuotaExceeded()
+    }
+    lift_ret.SetErr(lift_ret_val)
+  } else {
+    lift_ret.Set(struct{}{})
+  }
+  return lift_ret
+}
+
+func KvSetWithTtl(key string, value string, ttl_secs uint64) Result[struct{}, KvKvError] {
+  var lower_key C.bindings_string_t
+  
+  lower_key.ptr = C.CString(key)
+  lower_key.len = C.size_t(len(key))
+  defer C.bindings_string_free(&lower_key)
+  var lower_value C.bindings_string_t
+  
+  lower_value.ptr = C.CString(value)
+  lower_value.len = C.size_t(len(value))
+  defer C.bindings_string_free(&lower_value)
+  lower_ttl_secs := C.uint64_t(ttl_secs)
+  var ret C.bindings_result_void_kv_error_t
+  C.kv_set_with_ttl(&lower_key, &lower_value, lower_ttl_secs, &ret)
+  var lift_ret Result[struct{}, KvKvError]
+  if ret.is_err {
+    lift_ret_ptr := *(*C.kv_interface_kv_error_t)(unsafe.Pointer(&ret.val))
+    var lift_ret_val KvKvError
+    if lift_ret_ptr == 0 {
+      lift_ret_val = KvKvErrorNotAvailable()
+    }
+    if lift_ret_ptr == 1 {
+      lift_ret_val = KvKvErrorStorageError()
+    }
+    if lift_ret_ptr == 2 {
+      lift_ret_val = KvKvErrorQuotaExceeded()
+    }
+    lift_ret.SetErr(lift_ret_val)
+  } else {
+    lift_ret.Set(struct{}{})
+  }
+  return lift_ret
+}
+
+func KvDelete(key string) Result[struct{}, KvKvError] {
+  var lower_key C.bindings_string_t
+  
+  lower_key.ptr = C.CString(key)
+  lower_key.len = C.size_t(len(key))
+  defer C.bindings_string_free(&lower_key)
+  var ret C.bindings_result_void_kv_error_t
+  C.kv_delete(&lower_key, &ret)
+  var lift_ret Result[struct{}, KvKvError]
+  if ret.is_err {
+    lift_ret_ptr := *(*C.kv_interface_kv_error_t)(unsafe.Pointer(&ret.val))
+    var lift_ret_val KvKvError
+    if lift_ret_ptr == 0 {
+      lift_ret_val = KvKvErrorNotAvailable()
+    }
+    if lift_ret_ptr == 1 {
+      lift_ret_val = KvKvErrorStorageError()
+    }
+    if lift_ret_ptr == 2 {
+      lift_ret_val = KvKvErrorQuotaExceeded()
+    }
+    lift_ret.SetErr(lift_ret_val)
+  } else {
+    lift_ret.Set(struct{}{})
+  }
+  return lift_ret
+}
+
+func KvListKeys() []string {
+  var ret C.bindings_list_string_t
+  C.kv_list_keys(&ret)
+  var lift_ret []string
+  lift_ret = make([]string, ret.len)
+  if ret.len > 0 {
+    for lift_ret_i := 0; lift_ret_i < int(ret.len); lift_ret_i++ {
+      var empty_lift_ret C.bindings_string_t
+      lift_ret_ptr := *(*C.bindings_string_t)(unsafe.Pointer(uintptr(unsafe.Pointer(ret.ptr)) +
+      uintptr(lift_ret_i)*unsafe.Sizeof(empty_lift_ret)))
+      var list_lift_ret string
+      list_lift_ret = C.GoStringN(lift_ret_ptr.ptr, C.int(lift_ret_ptr.len))
+      lift_ret[lift_ret_i] = list_lift_ret
+    }
+  }
+  return lift_ret
+}
+
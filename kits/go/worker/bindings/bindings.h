@@ -58,6 +58,9 @@ typedef uint8_t http_types_http_error_t;
 #define HTTP_TYPES_HTTP_ERROR_INTERNAL_ERROR 4
 #define HTTP_TYPES_HTTP_ERROR_TIMEOUT 5
 #define HTTP_TYPES_HTTP_ERROR_REDIRECT_LOOP 6
+#define HTTP_TYPES_HTTP_ERROR_TOO_MANY_REDIRECTS 7
+#define HTTP_TYPES_HTTP_ERROR_RESPONSE_TOO_LARGE 8
+#define HTTP_TYPES_HTTP_ERROR_TOO_MANY_REQUESTS 9
 
 typedef struct {
   http_types_http_error_t error;
@@ -102,9 +105,39 @@ typedef struct {
   } val;
 } bindings_result_http_response_http_request_error_t;
 
+typedef uint8_t kv_interface_kv_error_t;
+
+#define KV_INTERFACE_KV_ERROR_NOT_AVAILABLE 0
+#define KV_INTERFACE_KV_ERROR_STORAGE_ERROR 1
+#define KV_INTERFACE_KV_ERROR_QUOTA_EXCEEDED 2
+
+typedef struct {
+  bool is_some;
+  bindings_string_t val;
+} bindings_option_string_t;
+
+typedef struct {
+  bool is_err;
+  union {
+    kv_interface_kv_error_t err;
+  } val;
+} bindings_result_void_kv_error_t;
+
+typedef struct {
+  bindings_string_t *ptr;
+  size_t len;
+} bindings_list_string_t;
+
 // Imported Functions from `http`
 void http_send_http_request(http_interface_http_request_t *request, bindings_result_http_response_http_request_error_t *ret);
 
+// Imported Functions from `kv`
+void kv_get(bindings_string_t *key, bindings_option_string_t *ret);
+void kv_set(bindings_string_t *key, bindings_string_t *value, bindings_result_void_kv_error_t *ret);
+void kv_set_with_ttl(bindings_string_t *key, bindings_string_t *value, uint64_t ttl_secs, bindings_result_void_kv_error_t *ret);
+void kv_delete(bindings_string_t *key, bindings_result_void_kv_error_t *ret);
+void kv_list_keys(bindings_list_string_t *ret);
+
 // Helper Functions
 
 void http_types_uri_free(http_types_uri_t *ptr);
@@ -121,6 +154,8 @@ void http_interface_http_request_free(http_interface_http_request_t *ptr);
 void http_interface_http_response_free(http_interface_http_response_t *ptr);
 void http_interface_http_request_error_free(http_interface_http_request_error_t *ptr);
 void bindings_result_http_response_http_request_error_free(bindings_result_http_response_http_request_error_t *ptr);
+void bindings_option_string_free(bindings_option_string_t *ptr);
+void bindings_list_string_free(bindings_list_string_t *ptr);
 void bindings_string_set(bindings_string_t *ret, const char*s);
 void bindings_string_dup(bindings_string_t *ret, const char*s);
 void bindings_string_free(bindings_string_t *ret);
@@ -0,0 +1,62 @@
+package worker
+
+import (
+	"errors"
+	"time"
+
+	"github.com/vmware-labs/wasm-workers-server/kits/go/worker/bindings"
+)
+
+var (
+	ErrKVNotAvailable  = errors.New("the K/V store is not available. Configure it in host mode")
+	ErrKVStorage       = errors.New("there was an error saving the K/V store")
+	ErrKVQuotaExceeded = errors.New("the K/V store exceeded its quota")
+)
+
+func kvError(result bindings.Result[struct{}, bindings.KvKvError]) error {
+	if result.IsOk() {
+		return nil
+	}
+
+	switch result.UnwrapErr().Kind() {
+	case bindings.KvKvErrorKindNotAvailable:
+		return ErrKVNotAvailable
+	case bindings.KvKvErrorKindQuotaExceeded:
+		return ErrKVQuotaExceeded
+	default:
+		return ErrKVStorage
+	}
+}
+
+// KVGet reads the value of the given key from the K/V store. The
+// second value reports if the key exists.
+func KVGet(key string) (string, bool) {
+	value := bindings.KvGet(key)
+
+	if value.IsNone() {
+		return "", false
+	}
+
+	return value.Unwrap(), true
+}
+
+// KVSet sets the value of the given key in the K/V store
+func KVSet(key string, value string) error {
+	return kvError(bindings.KvSet(key, value))
+}
+
+// KVSetWithTTL sets the value of the given key in the K/V store. The key
+// expires after the given duration
+func KVSetWithTTL(key string, value string, ttl time.Duration) error {
+	return kvError(bindings.KvSetWithTtl(key, value, uint64(ttl.Seconds())))
+}
+
+// KVDelete removes the given key from the K/V store
+func KVDelete(key string) error {
+	return kvError(bindings.KvDelete(key))
+}
+
+// KVListKeys lists all the keys in the K/V store
+func KVListKeys() []string {
+	return bindings.KvListKeys()
+}
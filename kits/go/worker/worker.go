@@ -10,7 +10,6 @@ import (
 	"io/ioutil"
 	"net/http"
 	"os"
-	"strings"
 	"unicode/utf8"
 
 	"github.com/vmware-labs/wasm-workers-server/kits/go/worker/bindings"
@@ -29,13 +28,13 @@ const (
 type input struct {
 	Url     string
 	Method  string
-	Headers map[string]string
-	Body    string
+	Headers map[string][]string
+	Body    []byte
 }
 
 type output struct {
 	Data    string
-	Headers map[string]string
+	Headers map[string][]string
 	Status  uint16
 	Base64  bool
 
@@ -74,8 +73,9 @@ func (o *output) Write(data []byte) (int, error) {
 		o.Status = 200
 	}
 
+	// Repeated headers like Set-Cookie are sent as a list of values
 	for k, v := range o.httpHeader {
-		o.Headers[k] = v[0]
+		o.Headers[k] = v
 	}
 
 	out, _ := sjson.Set("", "data", o.Data)
@@ -110,13 +110,26 @@ func readInput() (*input, error) {
 	in := &input{
 		Url:     gjson.GetBytes(stdin, "url").String(),
 		Method:  gjson.GetBytes(stdin, "method").String(),
-		Body:    gjson.GetBytes(stdin, "body").String(),
-		Headers: make(map[string]string),
+		Body:    []byte(gjson.GetBytes(stdin, "body").String()),
+		Headers: make(map[string][]string),
+	}
+
+	// Binary bodies are encoded as base64
+	if gjson.GetBytes(stdin, "base64").Bool() {
+		body, err := base64.StdEncoding.DecodeString(string(in.Body))
+		if err != nil {
+			return nil, err
+		}
+
+		in.Body = body
 	}
 
 	if gjson.GetBytes(stdin, "headers").Exists() {
+		// Repeated headers like Cookie are received as a list of values
 		gjson.GetBytes(stdin, "headers").ForEach(func(key, value gjson.Result) bool {
-			in.Headers[key.String()] = value.String()
+			for _, v := range value.Array() {
+				in.Headers[key.String()] = append(in.Headers[key.String()], v.String())
+			}
 			return true
 		})
 	}
@@ -139,13 +152,15 @@ func readInput() (*input, error) {
 }
 
 func createRequest(in *input) (*http.Request, error) {
-	req, err := http.NewRequest(in.Method, in.Url, strings.NewReader(in.Body))
+	req, err := http.NewRequest(in.Method, in.Url, bytes.NewReader(in.Body))
 	if err != nil {
 		return nil, err
 	}
 
-	for k, v := range in.Headers {
-		req.Header.Set(k, v)
+	for k, values := range in.Headers {
+		for _, v := range values {
+			req.Header.Add(k, v)
+		}
 	}
 
 	req = req.WithContext(context.WithValue(req.Context(), CacheKey, cache))
@@ -168,7 +183,7 @@ func getWriterRequest() (*output, *http.Request) {
 	}
 
 	w := &output{
-		Headers: make(map[string]string),
+		Headers: make(map[string][]string),
 	}
 
 	return w, req
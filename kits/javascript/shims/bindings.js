@@ -7,6 +7,13 @@ import { Response } from "./types/response";
 (function () {
   const __wws_send_http_request = globalThis.__wws_send_http_request;
   const __wws_console_log = globalThis.__wws_console_log;
+  const __wws_kv_get = globalThis.__wws_kv_get;
+  const __wws_kv_set = globalThis.__wws_kv_set;
+  const __wws_kv_set_with_ttl = globalThis.__wws_kv_set_with_ttl;
+  const __wws_kv_delete = globalThis.__wws_kv_delete;
+  const __wws_kv_list_keys = globalThis.__wws_kv_list_keys;
+  const __wws_sql_query = globalThis.__wws_sql_query;
+  const __wws_sql_execute = globalThis.__wws_sql_execute;
 
   globalThis.fetch = (uri, opts) => {
     let optsWithDefault = {
@@ -38,6 +45,59 @@ import { Response } from "./types/response";
     }
   }
 
+  // Access the K/V store through host functions. It requires to
+  // configure the store in host mode
+  globalThis.KV = {
+    get(key) {
+      return __wws_kv_get(key);
+    },
+    // The key expires after the optional `ttl` option in seconds
+    set(key, value, opts = {}) {
+      const error = opts.ttl === undefined
+        ? __wws_kv_set(key, value)
+        : __wws_kv_set_with_ttl(key, value, opts.ttl);
+
+      if (error !== null) {
+        throw new Error(error);
+      }
+    },
+    delete(key) {
+      const error = __wws_kv_delete(key);
+
+      if (error !== null) {
+        throw new Error(error);
+      }
+    },
+    keys() {
+      return __wws_kv_list_keys();
+    }
+  };
+
+  // Run SQL statements in the databases listed in the worker
+  // configuration. The host functions return a string on errors
+  globalThis.SQL = {
+    // Returns the rows as objects with the column names as keys
+    query(database, statement, params = []) {
+      const result = __wws_sql_query(database, statement, params);
+
+      if (typeof result === "string") {
+        throw new Error(result);
+      }
+
+      return result;
+    },
+    // Returns the number of changed rows
+    execute(database, statement, params = []) {
+      const result = __wws_sql_execute(database, statement, params);
+
+      if (typeof result === "string") {
+        throw new Error(result);
+      }
+
+      return result;
+    }
+  };
+
   globalThis.console = {
     error(msg) {
       this.log(msg);
@@ -61,4 +121,11 @@ import { Response } from "./types/response";
 
   Reflect.deleteProperty(globalThis, "__wws_send_http_request");
   Reflect.deleteProperty(globalThis, "__wws_console_log");
+  Reflect.deleteProperty(globalThis, "__wws_kv_get");
+  Reflect.deleteProperty(globalThis, "__wws_kv_set");
+  Reflect.deleteProperty(globalThis, "__wws_kv_set_with_ttl");
+  Reflect.deleteProperty(globalThis, "__wws_kv_delete");
+  Reflect.deleteProperty(globalThis, "__wws_kv_list_keys");
+  Reflect.deleteProperty(globalThis, "__wws_sql_query");
+  Reflect.deleteProperty(globalThis, "__wws_sql_execute");
 })();
@@ -11,22 +11,35 @@ class Headers {
     for (const key in initialHeaders) {
       let value = initialHeaders[key];
 
-      // Allow only string values
+      // Allow only string values or a list of them
       if (typeof value === "string") {
         headers[key] = value;
+      } else if (Array.isArray(value)) {
+        headers[key] = value.filter(v => typeof v === "string");
       }
     }
 
     this.headers = headers;
   }
 
+  // Add a new value to the header. Repeated headers like
+  // Set-Cookie are stored as a list of values
   append(key, value) {
-    this.headers[key] = value;
+    let current = this.headers[key];
+
+    if (current === undefined) {
+      this.headers[key] = value;
+    } else if (Array.isArray(current)) {
+      current.push(value);
+    } else {
+      this.headers[key] = [current, value];
+    }
+
     return value;
   }
 
   set(key, value) {
-    this.append(key, value);
+    this.headers[key] = value;
     return value;
   }
 
@@ -35,8 +48,19 @@ class Headers {
     return dropValue;
   }
 
+  // Repeated headers are combined in a single value. Cookies
+  // use their own separator (RFC 6265)
   get(key) {
-    return this.headers[key];
+    let value = this.headers[key];
+    let separator = key.toLowerCase() === "cookie" ? "; " : ", ";
+
+    return Array.isArray(value) ? value.join(separator) : value;
+  }
+
+  getSetCookie() {
+    let value = this.headers["Set-Cookie"] || this.headers["set-cookie"] || [];
+
+    return Array.isArray(value) ? value : [value];
   }
 
   toJSON() {
@@ -2,6 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 import { Headers } from "./headers";
+import { TextEncoder, TextDecoder } from "@sinonjs/text-encoding";
+
+const BASE64_CHARS = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Decode a base64 string into an array of bytes. Wasm Workers Server
+// encodes binary bodies as base64 before sending them to the worker.
+const decodeBase64 = (data) => {
+  const input = data.replace(/=+$/, "");
+  const bytes = new Uint8Array(Math.floor(input.length * 3 / 4));
+  let buffer = 0;
+  let bits = 0;
+  let index = 0;
+
+  for (const char of input) {
+    buffer = (buffer << 6) | BASE64_CHARS.indexOf(char);
+    bits += 6;
+
+    if (bits >= 8) {
+      bits -= 8;
+      bytes[index++] = (buffer >> bits) & 0xff;
+    }
+  }
+
+  return bytes;
+};
 
 // A request that comes from Wasm Workers Server.
 // It contains all the required data.
@@ -10,11 +35,24 @@ class Request {
     this.url = input.url;
     this.method = input.method;
     this.headers = new Headers(input.headers || {});
-    this.body = input.body;
+    // Binary bodies are exposed as an Uint8Array
+    this.body = input.base64 ? decodeBase64(input.body) : input.body;
     this.params = input.params || {};
   }
 
+  arrayBuffer() {
+    if (typeof this.body === "string") {
+      return new TextEncoder().encode(this.body);
+    }
+
+    return this.body;
+  }
+
   text() {
+    if (typeof this.body !== "string") {
+      return new TextDecoder("utf-8").decode(this.body);
+    }
+
     return this.body;
   }
 }
@@ -3,11 +3,75 @@
 
 use std::collections::HashMap;
 
-use crate::{error::RuntimeError, http::HttpError};
+use crate::{
+    error::RuntimeError,
+    http::HttpError,
+    kv::KvError,
+    sql::{SqlValueParam, SqlValueResult},
+};
 use javy::quickjs::{JSContextRef, JSValue, JSValueRef};
 
 // Bindings from WIT
-use crate::http;
+use crate::{http, kv, sql};
+
+/// Convert the result of a K/V change. The shim throws an error
+/// when it receives a string.
+fn kv_result(result: Result<(), KvError>) -> JSValue {
+    match result {
+        Ok(_) => JSValue::Null,
+        Err(KvError::NotAvailable) => {
+            JSValue::String("The K/V store is not available. Configure it in host mode".to_string())
+        }
+        Err(KvError::StorageError) => {
+            JSValue::String("There was an error saving the K/V store".to_string())
+        }
+        Err(KvError::QuotaExceeded) => {
+            JSValue::String("The K/V store exceeded its quota".to_string())
+        }
+    }
+}
+
+/// Convert the parameters of a SQL statement. JS numbers without decimals
+/// are sent as integers. It returns an error message for the values that
+/// don't have an equivalent in SQL.
+fn sql_params(params: &JSValue) -> Result<Vec<SqlValueParam<'_>>, String> {
+    let JSValue::Array(params) = params else {
+        return Ok(Vec::new());
+    };
+
+    params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| match param {
+            JSValue::Undefined | JSValue::Null => Ok(SqlValueParam::Null),
+            JSValue::Bool(value) => Ok(SqlValueParam::Integer(*value as i64)),
+            JSValue::Int(value) => Ok(SqlValueParam::Integer(*value as i64)),
+            JSValue::Float(value) if value.fract() == 0.0 && value.abs() < i64::MAX as f64 => {
+                Ok(SqlValueParam::Integer(*value as i64))
+            }
+            JSValue::Float(value) => Ok(SqlValueParam::Real(*value)),
+            JSValue::String(value) => Ok(SqlValueParam::Text(value)),
+            JSValue::ArrayBuffer(value) => Ok(SqlValueParam::Blob(value)),
+            _ => Err(format!(
+                "The SQL parameter at position {index} has an unsupported type"
+            )),
+        })
+        .collect()
+}
+
+/// Convert a value returned by a SQL query
+fn sql_value(value: SqlValueResult) -> JSValue {
+    match value {
+        SqlValueResult::Null => JSValue::Null,
+        SqlValueResult::Integer(value) => match i32::try_from(value) {
+            Ok(value) => JSValue::Int(value),
+            Err(_) => JSValue::Float(value as f64),
+        },
+        SqlValueResult::Real(value) => JSValue::Float(value),
+        SqlValueResult::Text(value) => JSValue::String(value),
+        SqlValueResult::Blob(value) => JSValue::ArrayBuffer(value),
+    }
+}
 
 /// Defines the different bindings required for the worker.
 /// It includes utilities to log information, make HTTP requests,
@@ -97,6 +161,9 @@ pub fn load_bindings_into_global(
                                 HttpError::InternalError => "Internal Error".to_string(),
                                 HttpError::Timeout => "Request Timeout".to_string(),
                                 HttpError::RedirectLoop => "Redirect Loop".to_string(),
+                                HttpError::TooManyRedirects => "Too Many Redirects".to_string(),
+                                HttpError::ResponseTooLarge => "Response Too Large".to_string(),
+                                HttpError::TooManyRequests => "Too Many Requests".to_string(),
                             };
 
                             Ok(JSValue::from_hashmap(HashMap::from([
@@ -115,6 +182,171 @@ pub fn load_bindings_into_global(
             invalid_export: "send_http_request".to_string(),
         })?;
 
+    global
+        .set_property(
+            "__wws_kv_get",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let key = args[0].to_string();
+
+                    match kv::get(&key) {
+                        Some(value) => Ok(JSValue::String(value)),
+                        None => Ok(JSValue::Undefined),
+                    }
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "kv_get".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "kv_get".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_kv_set",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let key = args[0].to_string();
+                    let value = args[1].to_string();
+
+                    Ok(kv_result(kv::set(&key, &value)))
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "kv_set".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "kv_set".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_kv_set_with_ttl",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let key = args[0].to_string();
+                    let value = args[1].to_string();
+                    let ttl_secs: f64 = args[2].try_into()?;
+
+                    Ok(kv_result(kv::set_with_ttl(
+                        &key,
+                        &value,
+                        ttl_secs.max(0.0) as u64,
+                    )))
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "kv_set_with_ttl".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "kv_set_with_ttl".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_kv_delete",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let key = args[0].to_string();
+
+                    Ok(kv_result(kv::delete(&key)))
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "kv_delete".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "kv_delete".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_kv_list_keys",
+            context
+                .wrap_callback(|_ctx, _this_arg, _args| {
+                    let keys = kv::list_keys().into_iter().map(JSValue::String).collect();
+
+                    Ok(JSValue::Array(keys))
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "kv_list_keys".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "kv_list_keys".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_sql_query",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let database = args[0].to_string();
+                    let statement = args[1].to_string();
+                    let params: JSValue = args[2].try_into()?;
+
+                    let params = match sql_params(&params) {
+                        Ok(params) => params,
+                        Err(message) => return Ok(JSValue::String(message)),
+                    };
+
+                    // Every row is an object with the column names as keys
+                    match sql::query(&database, &statement, &params) {
+                        Ok(result) => Ok(JSValue::Array(
+                            result
+                                .rows
+                                .into_iter()
+                                .map(|row| {
+                                    JSValue::from_hashmap(
+                                        result
+                                            .columns
+                                            .iter()
+                                            .map(String::as_str)
+                                            .zip(row.into_iter().map(sql_value))
+                                            .collect(),
+                                    )
+                                })
+                                .collect(),
+                        )),
+                        Err(err) => Ok(JSValue::String(err.message)),
+                    }
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "sql_query".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "sql_query".to_string(),
+        })?;
+
+    global
+        .set_property(
+            "__wws_sql_execute",
+            context
+                .wrap_callback(|_ctx, _this_arg, args| {
+                    let database = args[0].to_string();
+                    let statement = args[1].to_string();
+                    let params: JSValue = args[2].try_into()?;
+
+                    let params = match sql_params(&params) {
+                        Ok(params) => params,
+                        Err(message) => return Ok(JSValue::String(message)),
+                    };
+
+                    match sql::execute(&database, &statement, &params) {
+                        Ok(changes) => Ok(JSValue::Float(changes as f64)),
+                        Err(err) => Ok(JSValue::String(err.message)),
+                    }
+                })
+                .map_err(|_| RuntimeError::InvalidBinding {
+                    invalid_export: "sql_execute".to_string(),
+                })?,
+        )
+        .map_err(|_| RuntimeError::InvalidBinding {
+            invalid_export: "sql_execute".to_string(),
+        })?;
+
     global
         .set_property(
             "__wws_console_log",
@@ -15,6 +15,8 @@ use std::{
 
 // Load bindings from WIT file.
 wit_bindgen_rust::import!({paths: ["../../wit/core/http.wit"]});
+wit_bindgen_rust::import!({paths: ["../../wit/core/kv.wit"]});
+wit_bindgen_rust::import!({paths: ["../../wit/core/sql.wit"]});
 
 /// Ready to use runtime + polyfill
 static mut RUNTIME: OnceCell<Runtime> = OnceCell::new();
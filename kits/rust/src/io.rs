@@ -3,6 +3,7 @@
 
 use crate::Content;
 use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
 use http::Response;
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -15,13 +16,37 @@ use std::io::Stdin;
 pub struct Input {
     url: String,
     method: String,
-    headers: HashMap<String, String>,
+    /// Repeated headers like `Cookie` are received as a list of values
+    headers: HashMap<String, Vec<String>>,
     body: String,
+    #[serde(default)]
+    base64: bool,
     kv: HashMap<String, String>,
     #[serde(default)]
     params: HashMap<String, String>,
 }
 
+/// Types that can be used as the body of the http::Request
+/// passed to the handler. Use `Vec<u8>` to receive binary
+/// bodies like images or compressed files.
+pub trait Body: Sized {
+    /// Build the body from the raw bytes of the request
+    fn from_bytes(bytes: Vec<u8>) -> Self;
+}
+
+impl Body for String {
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+    }
+}
+
+impl Body for Vec<u8> {
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
 impl Input {
     /// Build the object from a JSON input
     pub fn new(reader: Stdin) -> Result<Self> {
@@ -29,17 +54,31 @@ impl Input {
     }
 
     /// Convers the current object to a valid http::Request
-    /// object
-    pub fn to_http_request(&self) -> http::Request<String> {
+    /// object. The body type is inferred from the handler
+    pub fn to_http_request<B: Body>(&self) -> http::Request<B> {
         let mut request = http::request::Builder::new()
             .uri(&self.url)
             .method(self.method.as_str());
 
-        for (key, value) in self.headers.iter() {
-            request = request.header(key, value);
+        for (key, values) in self.headers.iter() {
+            for value in values {
+                request = request.header(key, value);
+            }
         }
 
-        request.body(self.body.to_string()).unwrap()
+        request.body(B::from_bytes(self.body_bytes())).unwrap()
+    }
+
+    /// Retrieve the raw bytes of the body. Binary bodies
+    /// are sent as base64 by wws
+    pub fn body_bytes(&self) -> Vec<u8> {
+        if self.base64 {
+            general_purpose::STANDARD
+                .decode(&self.body)
+                .unwrap_or_default()
+        } else {
+            self.body.as_bytes().to_vec()
+        }
     }
 
     /// Retrieve the Key/Value data
@@ -58,7 +97,8 @@ impl Input {
 #[derive(Serialize, Deserialize)]
 pub struct Output {
     data: String,
-    headers: HashMap<String, String>,
+    /// Repeated headers like `Set-Cookie` are sent as a list of values
+    headers: HashMap<String, Vec<String>>,
     status: u16,
     kv: HashMap<String, String>,
     base64: bool,
@@ -76,7 +116,11 @@ impl Output {
         Self {
             data: data.to_string(),
             status,
-            headers: headers.unwrap_or_default(),
+            headers: headers
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, vec![value]))
+                .collect(),
             kv: kv.unwrap_or_default(),
             base64,
         }
@@ -84,13 +128,13 @@ impl Output {
 
     /// Build the struct from a http::Response object
     pub fn from_response(response: Response<Content>, cache: HashMap<String, String>) -> Self {
-        let mut headers = HashMap::new();
+        let mut headers: HashMap<String, Vec<String>> = HashMap::new();
 
         for (key, value) in response.headers().iter() {
-            headers.insert(
-                String::from(key.as_str()),
-                String::from(value.to_str().unwrap()),
-            );
+            headers
+                .entry(String::from(key.as_str()))
+                .or_default()
+                .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
         }
 
         // Note: added status here because `into_body` takes ownership of the
@@ -111,7 +155,13 @@ impl Output {
             }
         }
 
-        Self::new(&body, status, Some(headers), Some(cache), base64)
+        Self {
+            data: body,
+            status,
+            headers,
+            kv: cache,
+            base64,
+        }
     }
 
     /// Convert it to JSON
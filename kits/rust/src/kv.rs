@@ -0,0 +1,37 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Access the K/V store of the worker through host functions. It requires
+//! to set `mode = "host"` in the `[data.kv]` section of the worker
+//! configuration. Every change is saved right away, so concurrent requests
+//! don't override each other.
+
+// Load bindings from WIT file.
+wit_bindgen_rust::import!({paths: ["../../wit/core/kv.wit"]});
+
+pub use self::kv::KvError;
+
+/// Read the value of the given key
+pub fn get(key: &str) -> Option<String> {
+    kv::get(key)
+}
+
+/// Set the value of the given key
+pub fn set(key: &str, value: &str) -> Result<(), KvError> {
+    kv::set(key, value)
+}
+
+/// Set the value of the given key. It expires after the given seconds
+pub fn set_with_ttl(key: &str, value: &str, ttl_secs: u64) -> Result<(), KvError> {
+    kv::set_with_ttl(key, value, ttl_secs)
+}
+
+/// Remove the given key
+pub fn delete(key: &str) -> Result<(), KvError> {
+    kv::delete(key)
+}
+
+/// List all the keys in the namespace
+pub fn list_keys() -> Vec<String> {
+    kv::list_keys()
+}
@@ -14,3 +14,5 @@ pub use http;
 
 // Bindings!
 pub mod bindings;
+pub mod kv;
+pub mod sql;
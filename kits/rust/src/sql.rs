@@ -0,0 +1,34 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Run SQL statements in the databases of the project. The worker can
+//! only access the databases listed in the `[data.sql]` section of its
+//! configuration. Every database is a SQLite file in the `.wws/sql`
+//! folder of the project.
+
+// Load bindings from WIT file.
+wit_bindgen_rust::import!({paths: ["../../wit/core/sql.wit"]});
+
+pub use self::sql::{
+    SqlError, SqlRows, SqlStatementError, SqlValueParam as Param, SqlValueResult as Value,
+};
+
+/// Run a statement that returns rows, like `SELECT`. The `?` placeholders
+/// in the statement are replaced with the given parameters
+pub fn query(
+    database: &str,
+    statement: &str,
+    params: &[Param],
+) -> Result<SqlRows, SqlStatementError> {
+    sql::query(database, statement, params)
+}
+
+/// Run a statement that changes the database, like `INSERT` or
+/// `CREATE TABLE`. It returns the number of changed rows
+pub fn execute(
+    database: &str,
+    statement: &str,
+    params: &[Param],
+) -> Result<u64, SqlStatementError> {
+    sql::execute(database, statement, params)
+}
@@ -44,7 +44,7 @@ const RequestAndOutput = struct {
 pub const Input = struct {
     url: []const u8,
     method: []const u8,
-    headers: std.StringArrayHashMap([]const u8),
+    headers: http.Headers,
     body: []const u8,
 };
 
@@ -92,10 +92,6 @@ pub const Output = struct {
             self.setStatus(200);
         }
 
-        for (self.httpHeader.list.items) |item| {
-            try self.headers.put(item.name, item.value);
-        }
-
         // prepare writer for json
         var out_buf: [4096]u8 = undefined;
         var slice_stream = std.io.fixedBufferStream(&out_buf);
@@ -115,7 +111,7 @@ pub const Output = struct {
         try w.write(self.base64);
 
         try w.objectField("headers");
-        try w.write(try getHeadersJsonObject(self.headers));
+        try w.write(try getHeadersJsonObject(self.httpHeader));
 
         try w.objectField("kv");
         try w.write(try getCacheJsonObject(cache));
@@ -138,12 +134,142 @@ fn base64Encode(data: []const u8) []const u8 {
     return enc.encode(buf[0..data_len], data);
 }
 
-fn getHeadersJsonObject(s: std.StringArrayHashMap([]const u8)) !std.json.Value {
+fn base64Decode(data: []const u8) ![]const u8 {
+    var dec = std.base64.standard.Decoder;
+    var data_len = try dec.calcSizeForSlice(data);
+    var buf = try allocator.alloc(u8, data_len);
+    try dec.decode(buf, data);
+    return buf;
+}
+
+// The K/V host functions are defined in the wit/core/kv.wit file. They
+// follow the canonical ABI, so the host allocates the returned strings
+// through the canonical_abi_realloc export.
+const kv_host = struct {
+    extern "kv" fn get(key_ptr: [*]const u8, key_len: usize, ret: *[3]u32) void;
+    extern "kv" fn set(key_ptr: [*]const u8, key_len: usize, value_ptr: [*]const u8, value_len: usize, ret: *[2]u8) void;
+    extern "kv" fn @"set-with-ttl"(key_ptr: [*]const u8, key_len: usize, value_ptr: [*]const u8, value_len: usize, ttl_secs: u64, ret: *[2]u8) void;
+    extern "kv" fn delete(key_ptr: [*]const u8, key_len: usize, ret: *[2]u8) void;
+    extern "kv" fn @"list-keys"(ret: *[2]u32) void;
+};
+
+export fn canonical_abi_realloc(ptr: ?[*]u8, old_size: usize, alignment: usize, new_size: usize) ?[*]u8 {
+    if (new_size == 0) {
+        return @ptrFromInt(alignment);
+    }
+
+    // The arena frees everything at the end, so it always allocates a new buffer
+    var buf = allocator.alignedAlloc(u8, 8, new_size) catch return null;
+    if (ptr) |old| {
+        @memcpy(buf[0..@min(old_size, new_size)], old[0..@min(old_size, new_size)]);
+    }
+
+    return buf.ptr;
+}
+
+pub const KVError = error{
+    // The worker doesn't have a K/V store in host mode
+    NotAvailable,
+    // The change couldn't be saved in the storage backend
+    StorageError,
+    // The change exceeds the quota of the namespace
+    QuotaExceeded,
+};
+
+fn liftString(ptr: u32, len: u32) []const u8 {
+    if (len == 0) {
+        return "";
+    }
+
+    const data: [*]const u8 = @ptrFromInt(ptr);
+    return data[0..len];
+}
+
+fn kvResult(ret: [2]u8) KVError!void {
+    if (ret[0] == 0) {
+        return;
+    }
+
+    return switch (ret[1]) {
+        0 => KVError.NotAvailable,
+        2 => KVError.QuotaExceeded,
+        else => KVError.StorageError,
+    };
+}
+
+// Read the value of the given key from the K/V store
+pub fn kvGet(key: []const u8) ?[]const u8 {
+    var ret: [3]u32 = undefined;
+    kv_host.get(key.ptr, key.len, &ret);
+
+    if (ret[0] & 0xff == 0) {
+        return null;
+    }
+
+    return liftString(ret[1], ret[2]);
+}
+
+// Set the value of the given key in the K/V store
+pub fn kvSet(key: []const u8, value: []const u8) KVError!void {
+    var ret: [2]u8 = undefined;
+    kv_host.set(key.ptr, key.len, value.ptr, value.len, &ret);
+
+    return kvResult(ret);
+}
+
+// Set the value of the given key in the K/V store. It expires after the given seconds
+pub fn kvSetWithTtl(key: []const u8, value: []const u8, ttl_secs: u64) KVError!void {
+    var ret: [2]u8 = undefined;
+    kv_host.@"set-with-ttl"(key.ptr, key.len, value.ptr, value.len, ttl_secs, &ret);
+
+    return kvResult(ret);
+}
+
+// Remove the given key from the K/V store
+pub fn kvDelete(key: []const u8) KVError!void {
+    var ret: [2]u8 = undefined;
+    kv_host.delete(key.ptr, key.len, &ret);
+
+    return kvResult(ret);
+}
+
+// List all the keys in the K/V store
+pub fn kvListKeys() ![][]const u8 {
+    var ret: [2]u32 = undefined;
+    kv_host.@"list-keys"(&ret);
+
+    var keys = try allocator.alloc([]const u8, ret[1]);
+    if (ret[1] == 0) {
+        return keys;
+    }
+
+    const items: [*]const [2]u32 = @ptrFromInt(ret[0]);
+    for (keys, 0..) |*key, i| {
+        key.* = liftString(items[i][0], items[i][1]);
+    }
+
+    return keys;
+}
+
+fn getHeadersJsonObject(headers: http.Headers) !std.json.Value {
     var value = std.json.Value{ .object = std.json.ObjectMap.init(allocator) };
 
-    var i = s.iterator();
-    while (i.next()) |kv| {
-        try value.object.put(kv.key_ptr.*, std.json.Value{ .string = kv.value_ptr.*});
+    for (headers.list.items) |item| {
+        if (value.object.getPtr(item.name)) |existing| {
+            // Repeated headers like Set-Cookie are sent as a list of values
+            switch (existing.*) {
+                .string => |first| {
+                    var values = std.json.Array.init(allocator);
+                    try values.append(std.json.Value{ .string = first });
+                    try values.append(std.json.Value{ .string = item.value });
+                    existing.* = std.json.Value{ .array = values };
+                },
+                .array => |*values| try values.append(std.json.Value{ .string = item.value }),
+                else => {},
+            }
+        } else {
+            try value.object.put(item.name, std.json.Value{ .string = item.value });
+        }
     }
 
     return value;
@@ -177,13 +303,23 @@ fn getInput(s: []const u8) !Input {
         .url = parsed.value.object.get("url").?.string,
         .method = parsed.value.object.get("method").?.string,
         .body = parsed.value.object.get("body").?.string,
-        .headers = std.StringArrayHashMap([]const u8).init(allocator),
+        .headers = http.Headers.init(allocator),
     };
 
+    // Binary bodies are encoded as base64
+    if (parsed.value.object.get("base64")) |base64| {
+        if (base64.bool) {
+            input.body = try base64Decode(input.body);
+        }
+    }
+
     var headers_map = parsed.value.object.get("headers").?.object;
     var headersIterator = headers_map.iterator();
     while (headersIterator.next()) |entry| {
-        try input.headers.put(entry.key_ptr.*, entry.value_ptr.*.string);
+        // Repeated headers like Cookie are received as a list of values
+        for (entry.value_ptr.*.array.items) |value| {
+            try input.headers.append(entry.key_ptr.*, value.string);
+        }
     }
 
     var kv = parsed.value.object.get("kv").?.object;
@@ -210,9 +346,8 @@ pub fn createRequest(in: *Input) !Request {
         .context = Context.init(),
     };
 
-    var i = in.headers.iterator();
-    while (i.next()) |kv| {
-        try req.headers.append(kv.key_ptr.*, kv.value_ptr.*);
+    for (in.headers.list.items) |item| {
+        try req.headers.append(item.name, item.value);
     }
 
     return req;
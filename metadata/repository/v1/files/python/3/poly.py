@@ -24,7 +24,12 @@ class Request:
         self.method = input["method"]
         self.url = input["url"]
         self.body = input["body"]
-        self.headers = input["headers"]
+        # Repeated headers are received as a list of values. They're
+        # combined in a single value. Cookies use their own separator
+        self.headers = {
+            key: ("; " if key == "cookie" else ", ").join(values)
+            for key, values in input["headers"].items()
+        }
         self.params = input["params"]
 
         # Init the cache
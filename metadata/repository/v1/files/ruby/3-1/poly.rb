@@ -7,7 +7,9 @@ def json_to_request(input)
     json['url'],
     json['body'],
     json['method'],
-    json['headers'],
+    # Repeated headers are received as a list of values. They're
+    # combined in a single value. Cookies use their own separator
+    json['headers'].to_h { |key, values| [key, values.join(key == 'cookie' ? '; ' : ', ')] },
     json['params'],
     json['kv']
   )
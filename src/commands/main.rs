@@ -1,7 +1,7 @@
 // Copyright 2022 VMware, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use super::runtimes::Runtimes;
+use super::{precompile::Precompile, runtimes::Runtimes};
 use clap::Subcommand;
 
 /// Available subcommands in the CLI
@@ -9,4 +9,6 @@ use clap::Subcommand;
 pub enum Main {
     #[clap(name = "runtimes")]
     Runtimes(Runtimes),
+    #[clap(name = "precompile")]
+    Precompile(Precompile),
 }
@@ -3,4 +3,5 @@
 
 // The different commands for the `wws` CLI.
 pub(crate) mod main;
+pub(crate) mod precompile;
 pub(crate) mod runtimes;
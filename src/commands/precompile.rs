@@ -0,0 +1,87 @@
+// Copyright 2023 VMware, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::path::{Path, PathBuf};
+use wws_config::Config;
+use wws_router::{nested_ignore_patterns, Routes};
+
+/// Compile the workers and cache the result in the project store
+///
+/// The compiled artifacts are saved in the `.wws` folder. Next runs
+/// load them instead of compiling the modules again.
+#[derive(Args, Debug)]
+pub struct Precompile {}
+
+impl Precompile {
+    /// Load the workers from the given project and the projects of its
+    /// virtual hosts. Loading a worker compiles its module and saves the
+    /// result in the project store.
+    pub fn run(&self, project_root: &Path, ignore_patterns: Vec<String>) -> Result<()> {
+        let config = match Config::load(project_root) {
+            Ok(c) => c,
+            Err(err) => {
+                println!("⚠️  There was an error reading the .wws.toml file. It will be ignored");
+                println!("⚠️  Error: {err}");
+
+                Config::default()
+            }
+        };
+
+        if config.is_missing_any_runtime(project_root) {
+            println!("⚠️  Required language runtimes are not installed. Some files may not be considered workers");
+            println!("⚠️  You can install the missing runtimes with `wws runtimes install`");
+        }
+
+        // Like the server, every project ignores the workers of the
+        // projects nested in it
+        let host_paths: Vec<PathBuf> = config
+            .hosts
+            .iter()
+            .map(|config_host| project_root.join(&config_host.root))
+            .collect();
+        let ignore_patterns = |path: &Path| {
+            let mut patterns = ignore_patterns.clone();
+            patterns.extend(nested_ignore_patterns(path, &host_paths));
+            patterns
+        };
+
+        let mut workers = count_workers(
+            &Routes::new(project_root, "", ignore_patterns(project_root), &config)
+                .map_err(|err| anyhow!(err))?,
+        );
+
+        for (config_host, host_path) in config.hosts.iter().zip(host_paths.iter()) {
+            let host = &config_host.host;
+            let host_config = match Config::load(host_path) {
+                Ok(c) => c,
+                Err(err) => {
+                    println!(
+                        "⚠️  There was an error reading the .wws.toml file of {host}. It will be ignored"
+                    );
+                    println!("⚠️  Error: {err}");
+
+                    Config::default()
+                }
+            };
+
+            if host_config.is_missing_any_runtime(host_path) {
+                println!("⚠️  Required language runtimes are not installed for {host}. Some files may not be considered workers");
+            }
+
+            workers += count_workers(
+                &Routes::new(host_path, "", ignore_patterns(host_path), &host_config)
+                    .map_err(|err| anyhow!("{host}: {err}"))?,
+            );
+        }
+
+        println!("✅ Precompiled {workers} workers");
+        Ok(())
+    }
+}
+
+/// Number of workers in the given routes
+fn count_workers(routes: &Routes) -> usize {
+    routes.routes.len() + routes.middlewares.len() + routes.error_workers.len()
+}
@@ -10,12 +10,13 @@ use clap::Parser;
 use commands::main::Main;
 use commands::runtimes::RuntimesCommands;
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::{Arc, RwLock};
 use wws_config::Config;
 use wws_project::{identify_type, prepare_project, ProjectType};
-use wws_router::Routes;
-use wws_server::{serve, ServeOptions};
+use wws_router::{nested_ignore_patterns, watch, ErrorKind as RouteErrorKind, Routes};
+use wws_server::{serve, HostPattern, HttpMode, ServeOptions, VirtualHost};
 
 // Arguments
 #[derive(Parser, Debug)]
@@ -65,13 +66,26 @@ pub struct Args {
     #[arg(long)]
     enable_panel: bool,
 
-    /// Manage language runtimes in your project
+    /// Reload the workers when their files change
+    #[arg(long)]
+    watch: bool,
+
+    /// Manage language runtimes and precompile the workers in your project
     #[command(subcommand)]
     commands: Option<Main>,
 
     /// CORS headers to add to all workers if not already set by the worker
     #[arg(long)]
     cors: Option<Vec<String>>,
+
+    /// Save the HTTP requests of the workers and their responses in the given folder
+    #[arg(long, value_name = "DIR", conflicts_with = "http_replay")]
+    http_record: Option<PathBuf>,
+
+    /// Reply to the HTTP requests of the workers with the responses in the given folder.
+    /// The requests never reach the network
+    #[arg(long, value_name = "DIR")]
+    http_replay: Option<PathBuf>,
 }
 
 #[actix_web::main]
@@ -132,6 +146,26 @@ async fn main() -> std::io::Result<()> {
         };
 
         run_result
+    } else if let Some(Main::Precompile(precompile)) = &args.commands {
+        match identify_type(&args.path) {
+            Ok(ProjectType::Local) => {}
+            Ok(_) => {
+                eprintln!("❌ You can only precompile workers in local projects");
+                exit(1);
+            }
+            Err(err) => {
+                eprintln!("❌ There was an error preparing the project: {err}");
+                exit(1);
+            }
+        }
+
+        if let Err(err) = precompile.run(&args.path, args.ignore.clone()) {
+            println!("❌ There was an error precompiling the workers");
+            println!("👉 {err}");
+            return Err(Error::new(ErrorKind::InvalidData, ""));
+        }
+
+        Ok(())
     } else {
         // TODO(Angelmmiguel): refactor this into a separate command!
 
@@ -176,18 +210,131 @@ async fn main() -> std::io::Result<()> {
             }
         }
 
+        // The projects of the virtual hosts may be inside the main one. Every
+        // project ignores the workers of the projects nested in it
+        let host_paths: Vec<PathBuf> = config
+            .hosts
+            .iter()
+            .map(|config_host| project_path.join(&config_host.root))
+            .collect();
+        let ignore_patterns = |path: &Path| {
+            let mut patterns = args.ignore.clone();
+            patterns.extend(nested_ignore_patterns(path, &host_paths));
+            patterns
+        };
+
         println!("⚙️  Loading routes from: {}", &project_path.display());
-        let routes = Routes::new(&project_path, &args.prefix, args.ignore, &config);
-        for route in routes.routes.iter() {
+        let routes = Arc::new(RwLock::new(load_routes(
+            &project_path,
+            &config,
+            &args,
+            ignore_patterns(&project_path),
+            &args.hostname,
+        )));
+        let kv_backend = config.kv_backend();
+
+        // Load the projects of the virtual hosts. Each one has its own
+        // configuration, routes and data
+        let mut hosts = Vec::new();
+        for (config_host, host_path) in config.hosts.iter().zip(host_paths.iter()) {
+            let host = match config_host.host.parse::<HostPattern>() {
+                Ok(host) => host,
+                Err(err) => {
+                    eprintln!("❌ There was an error reading the hosts: {err}");
+
+                    exit(1);
+                }
+            };
+            let host_path = host_path.clone();
+            let host_config = match Config::load(&host_path) {
+                Ok(c) => c,
+                Err(err) => {
+                    println!(
+                        "⚠️  There was an error reading the .wws.toml file of {host}. It will be ignored"
+                    );
+                    println!("⚠️  Error: {err}");
+
+                    Config::default()
+                }
+            };
+
+            if host_config.is_missing_any_runtime(&host_path) {
+                println!("⚠️  Required language runtimes are not installed for {host}. Some files may not be considered workers");
+            }
+
             println!(
-                "    - http://{}:{}{}\n      => {}",
-                &args.hostname,
-                args.port,
-                route.path,
-                route.handler.display()
+                "⚙️  Loading routes for {host} from: {}",
+                &host_path.display()
             );
+            let host_routes = Arc::new(RwLock::new(load_routes(
+                &host_path,
+                &host_config,
+                &args,
+                ignore_patterns(&host_path),
+                &host.to_string(),
+            )));
+
+            hosts.push((
+                VirtualHost {
+                    host,
+                    root_path: host_path,
+                    routes: host_routes,
+                    kv_backend: host_config.kv_backend(),
+                },
+                host_config,
+            ));
+        }
+
+        // The watchers stop when they're dropped, so keep them until the server finishes
+        let mut watchers = Vec::new();
+        if args.watch {
+            let projects = [(&routes, &project_path, config)].into_iter().chain(
+                hosts
+                    .iter()
+                    .map(|(host, config)| (&host.routes, &host.root_path, config.clone())),
+            );
+
+            for (routes, path, config) in projects {
+                match watch(
+                    Arc::clone(routes),
+                    path.clone(),
+                    ignore_patterns(path),
+                    config,
+                ) {
+                    Ok(watcher) => {
+                        println!("👀 Watching for changes in {}", path.display());
+                        watchers.push(watcher);
+                    }
+                    Err(err) => {
+                        eprintln!("❌ There was an error watching the project files: {err}");
+
+                        exit(1);
+                    }
+                }
+            }
         }
 
+        let http_mode = match (args.http_record, args.http_replay) {
+            (Some(folder), _) => {
+                println!("📼 Recording the HTTP requests in {}", folder.display());
+                HttpMode::Record(folder)
+            }
+            (_, Some(folder)) => {
+                if !folder.is_dir() {
+                    eprintln!(
+                        "❌ The folder to replay the HTTP requests doesn't exist: {}",
+                        folder.display()
+                    );
+
+                    exit(1);
+                }
+
+                println!("📼 Replaying the HTTP requests from {}", folder.display());
+                HttpMode::Replay(folder)
+            }
+            _ => HttpMode::Live,
+        };
+
         if args.enable_panel {
             println!(
                 "🎛️  The admin panel is available at http://{}:{}/_panel/",
@@ -202,6 +349,9 @@ async fn main() -> std::io::Result<()> {
             port: args.port,
             panel: args.enable_panel.into(),
             cors_origins: args.cors,
+            kv_backend,
+            http_mode,
+            hosts: hosts.into_iter().map(|(host, _)| host).collect(),
         })
         .await
         .map_err(|err| Error::new(ErrorKind::AddrInUse, err))?;
@@ -215,3 +365,56 @@ async fn main() -> std::io::Result<()> {
         server.await
     }
 }
+
+/// Load the routes of a project and print them. The URLs use the given
+/// host name. It exits if the routes are not valid.
+fn load_routes(
+    project_path: &Path,
+    config: &Config,
+    args: &Args,
+    ignore_patterns: Vec<String>,
+    hostname: &str,
+) -> Routes {
+    let routes = match Routes::new(project_path, &args.prefix, ignore_patterns, config) {
+        Ok(routes) => routes,
+        Err(err) => {
+            eprintln!("❌ There was an error loading the routes: {err}");
+
+            exit(1);
+        }
+    };
+
+    for route in routes.routes.iter() {
+        println!(
+            "    - http://{}:{}{}\n      => {}",
+            hostname,
+            args.port,
+            route.path,
+            route.handler.display()
+        );
+    }
+    for middleware in routes.middlewares.iter() {
+        println!(
+            "    - http://{}:{}{}/* (middleware)\n      => {}",
+            hostname,
+            args.port,
+            middleware.path,
+            middleware.handler.display()
+        );
+    }
+    for error_worker in routes.error_workers.iter() {
+        let kind = match error_worker.kind {
+            RouteErrorKind::NotFound => "404 worker",
+            RouteErrorKind::Error => "error worker",
+        };
+        println!(
+            "    - http://{}:{}{}/* ({kind})\n      => {}",
+            hostname,
+            args.port,
+            error_worker.path,
+            error_worker.handler.display()
+        );
+    }
+
+    routes
+}